@@ -0,0 +1,296 @@
+use crate::color::{delta_e76, Lab};
+use crate::Error;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// The colors of the standard alphabet, one per base64 symbol and the padding
+// character. Both directions of the mapping are built from this table.
+const STANDARD_TABLE: [(char, (u8, u8, u8)); 65] = [
+    ('a', (204, 180, 194)),
+    ('A', (255, 255, 255)),
+    ('b', (197, 186, 201)),
+    ('B', (221, 206, 212)),
+    ('c', (181, 185, 193)),
+    ('C', (184, 201, 223)),
+    ('d', (224, 218, 192)),
+    ('D', (185, 191, 195)),
+    ('e', (181, 197, 198)),
+    ('E', (193, 206, 255)),
+    ('f', (252, 193, 211)),
+    ('F', (183, 192, 229)),
+    ('g', (180, 191, 192)),
+    ('G', (187, 219, 189)),
+    ('h', (195, 187, 234)),
+    ('H', (182, 216, 189)),
+    ('i', (197, 183, 248)),
+    ('I', (200, 182, 204)),
+    ('j', (255, 235, 196)),
+    ('J', (194, 186, 228)),
+    ('k', (199, 238, 239)),
+    ('K', (208, 247, 234)),
+    ('l', (244, 214, 189)),
+    ('L', (187, 243, 239)),
+    ('m', (188, 231, 238)),
+    ('M', (187, 197, 227)),
+    ('n', (186, 240, 191)),
+    ('N', (187, 198, 206)),
+    ('o', (205, 193, 184)),
+    ('O', (191, 187, 197)),
+    ('p', (194, 200, 206)),
+    ('P', (195, 183, 229)),
+    ('q', (182, 219, 196)),
+    ('Q', (238, 216, 184)),
+    ('r', (199, 181, 208)),
+    ('R', (239, 231, 198)),
+    ('s', (189, 188, 230)),
+    ('S', (242, 192, 230)),
+    ('t', (199, 199, 199)),
+    ('T', (188, 190, 230)),
+    ('u', (230, 180, 253)),
+    ('U', (241, 247, 247)),
+    ('v', (242, 190, 199)),
+    ('V', (230, 247, 234)),
+    ('w', (197, 186, 249)),
+    ('W', (194, 247, 249)),
+    ('x', (242, 182, 246)),
+    ('X', (188, 222, 193)),
+    ('y', (188, 194, 183)),
+    ('Y', (197, 195, 197)),
+    ('z', (187, 249, 240)),
+    ('Z', (233, 231, 242)),
+    ('0', (195, 184, 218)),
+    ('1', (232, 180, 196)),
+    ('2', (191, 193, 196)),
+    ('3', (185, 186, 186)),
+    ('4', (191, 247, 180)),
+    ('5', (187, 199, 248)),
+    ('6', (248, 198, 184)),
+    ('7', (243, 195, 184)),
+    ('8', (232, 192, 208)),
+    ('9', (239, 197, 183)),
+    ('/', (199, 187, 241)),
+    ('+', (195, 216, 223)),
+    ('=', (193, 211, 184)),
+];
+
+// A one-to-one mapping between symbols and the colors that draw them in the
+// first row of an image
+#[derive(Clone, Debug)]
+pub struct ColorAlphabet {
+    entries: Vec<(char, (u8, u8, u8))>,
+    colors: HashMap<char, (u8, u8, u8)>,
+    symbols: HashMap<(u8, u8, u8), char>,
+}
+
+impl ColorAlphabet {
+    // Minimum CIE76 distance between any two colors of a palette loaded from
+    // a file that does not set its own `min_distance`
+    pub const DEFAULT_MIN_DISTANCE: f64 = 10.0;
+
+    // Fails with `Error::InvalidAlphabet` if a symbol or a color appears more
+    // than once, since that entry could not be decoded
+    pub fn new(entries: &[(char, (u8, u8, u8))]) -> Result<ColorAlphabet, Error> {
+        let mut colors = HashMap::with_capacity(entries.len());
+        let mut symbols = HashMap::with_capacity(entries.len());
+        for &(symbol, color) in entries {
+            if colors.insert(symbol, color).is_some() || symbols.insert(color, symbol).is_some() {
+                return Err(Error::InvalidAlphabet);
+            }
+        }
+        Ok(ColorAlphabet {
+            entries: entries.to_vec(),
+            colors,
+            symbols,
+        })
+    }
+
+    // `new` that also requires every two colors to be at least
+    // `min_distance` apart in CIELAB (CIE76), failing with
+    // `Error::ColorsTooClose` for the closest pair otherwise. Colors that look
+    // alike are confused once an image is recompressed or resized.
+    pub fn with_min_distance(
+        entries: &[(char, (u8, u8, u8))],
+        min_distance: f64,
+    ) -> Result<ColorAlphabet, Error> {
+        let alphabet = ColorAlphabet::new(entries)?;
+        match alphabet.closest_pair() {
+            Some((first, second, distance)) if distance < min_distance => {
+                Err(Error::ColorsTooClose {
+                    first,
+                    second,
+                    distance,
+                })
+            }
+            _ => Ok(alphabet),
+        }
+    }
+
+    // A palette in TOML, with the colors as `[r, g, b]` or `"#rrggbb"`:
+    //
+    //   min_distance = 12.0
+    //
+    //   [colors]
+    //   A = "#0b3d91"
+    //   "+" = [250, 70, 22]
+    //
+    // `min_distance` is optional and defaults to `DEFAULT_MIN_DISTANCE`.
+    pub fn from_toml(text: &str) -> Result<ColorAlphabet, Error> {
+        let file: PaletteFile = toml::from_str(text).map_err(|_| Error::InvalidAlphabet)?;
+        file.into_alphabet()
+    }
+
+    // `from_toml` for the same fields in JSON,
+    // `{"min_distance": 12.0, "colors": {"A": "#0b3d91", "+": [250, 70, 22]}}`
+    pub fn from_json(text: &str) -> Result<ColorAlphabet, Error> {
+        let file: PaletteFile = serde_json::from_str(text).map_err(|_| Error::InvalidAlphabet)?;
+        file.into_alphabet()
+    }
+
+    // Read a palette file, as TOML or JSON by its `.toml` or `.json` extension
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ColorAlphabet, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => ColorAlphabet::from_toml(&text),
+            Some("json") => ColorAlphabet::from_json(&text),
+            _ => Err(Error::InvalidAlphabet),
+        }
+    }
+
+    // The alphabet of `create_img` and `decode_image_and_extract_text`
+    pub fn standard() -> &'static ColorAlphabet {
+        static STANDARD: OnceLock<ColorAlphabet> = OnceLock::new();
+        STANDARD.get_or_init(|| ColorAlphabet::new(&STANDARD_TABLE).unwrap())
+    }
+
+    pub fn color(&self, symbol: char) -> Option<(u8, u8, u8)> {
+        self.colors.get(&symbol).copied()
+    }
+
+    pub fn symbol(&self, color: (u8, u8, u8)) -> Option<char> {
+        self.symbols.get(&color).copied()
+    }
+
+    // The entries in the order they were given
+    pub fn entries(&self) -> &[(char, (u8, u8, u8))] {
+        &self.entries
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = char> + '_ {
+        self.entries.iter().map(|(symbol, _)| *symbol)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The two symbols whose colors are closest in CIELAB and their CIE76
+    // distance, or `None` for fewer than two symbols
+    pub fn closest_pair(&self) -> Option<(char, char, f64)> {
+        let labs: Vec<(char, Lab)> = self
+            .entries
+            .iter()
+            .map(|&(symbol, color)| (symbol, Lab::from_rgb(color)))
+            .collect();
+        let mut closest: Option<(char, char, f64)> = None;
+        for (i, &(first, x)) in labs.iter().enumerate() {
+            for &(second, y) in &labs[i + 1..] {
+                let distance = delta_e76(x, y);
+                let closer = match closest {
+                    Some((_, _, min)) => distance < min,
+                    None => true,
+                };
+                if closer {
+                    closest = Some((first, second, distance));
+                }
+            }
+        }
+        closest
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteFile {
+    min_distance: Option<f64>,
+    colors: PaletteEntries,
+}
+
+impl PaletteFile {
+    fn into_alphabet(self) -> Result<ColorAlphabet, Error> {
+        let mut entries = Vec::with_capacity(self.colors.0.len());
+        for (symbol, color) in self.colors.0 {
+            let mut chars = symbol.chars();
+            let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                return Err(Error::InvalidAlphabet);
+            };
+            entries.push((symbol, color.rgb()?));
+        }
+        let min_distance = self
+            .min_distance
+            .unwrap_or(ColorAlphabet::DEFAULT_MIN_DISTANCE);
+        ColorAlphabet::with_min_distance(&entries, min_distance)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaletteColor {
+    Rgb([u8; 3]),
+    Hex(String),
+}
+
+impl PaletteColor {
+    fn rgb(&self) -> Result<(u8, u8, u8), Error> {
+        match self {
+            PaletteColor::Rgb([r, g, b]) => Ok((*r, *g, *b)),
+            PaletteColor::Hex(hex) => {
+                let digits = hex.strip_prefix('#').ok_or(Error::InvalidAlphabet)?;
+                if digits.len() != 6 || !digits.is_ascii() {
+                    return Err(Error::InvalidAlphabet);
+                }
+                let channel = |i: usize| {
+                    u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| Error::InvalidAlphabet)
+                };
+                Ok((channel(0)?, channel(2)?, channel(4)?))
+            }
+        }
+    }
+}
+
+// The `colors` table in file order. Unlike a map type it keeps repeated
+// symbols, which JSON allows, so that `ColorAlphabet::new` rejects them.
+struct PaletteEntries(Vec<(String, PaletteColor)>);
+
+impl<'de> Deserialize<'de> for PaletteEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = PaletteEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a table of symbols to colors")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(PaletteEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
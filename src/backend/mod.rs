@@ -0,0 +1,28 @@
+// The primitives every construction in this crate is built from. OpenSSL is
+// the default; without the `openssl` feature the `rustcrypto` feature swaps in
+// pure-Rust implementations. Both produce byte-identical envelopes, so either
+// build opens what the other wrote.
+//
+// Public key encryption and signatures still need OpenSSL.
+
+#[cfg(feature = "openssl")]
+mod openssl;
+#[cfg(feature = "openssl")]
+pub(crate) use self::openssl::*;
+
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+mod rustcrypto;
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+pub(crate) use self::rustcrypto::*;
+
+#[cfg(not(any(feature = "openssl", feature = "rustcrypto")))]
+compile_error!("enable either the `openssl` or the `rustcrypto` feature");
+
+pub(crate) const TAG_LEN: usize = 16;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Aead {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
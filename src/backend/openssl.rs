@@ -0,0 +1,93 @@
+use super::{Aead, TAG_LEN};
+use crate::Error;
+use openssl::hash::MessageDigest;
+use openssl::md::Md;
+use openssl::memcmp;
+use openssl::pkey::{Id, PKey};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use openssl::symm::{decrypt, decrypt_aead, encrypt, encrypt_aead, Cipher};
+
+pub(crate) fn random_bytes(buf: &mut [u8]) -> Result<(), Error> {
+    Ok(rand_bytes(buf)?)
+}
+
+// AES-128-CBC with PKCS#7 padding
+pub(crate) fn aes_128_cbc_encrypt(
+    key: &[u8],
+    iv: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    Ok(encrypt(Cipher::aes_128_cbc(), key, Some(iv), plaintext)?)
+}
+
+pub(crate) fn aes_128_cbc_decrypt(
+    key: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    decrypt(Cipher::aes_128_cbc(), key, Some(iv), ciphertext).map_err(|_| Error::Decryption)
+}
+
+// Returns the ciphertext followed by the tag
+pub(crate) fn aead_seal(
+    aead: Aead,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut tag = [0; TAG_LEN];
+    let mut result = encrypt_aead(cipher(aead), key, Some(nonce), aad, plaintext, &mut tag)?;
+    result.extend_from_slice(&tag);
+    Ok(result)
+}
+
+pub(crate) fn aead_open(
+    aead: Aead,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    // OpenSSL only fails here when the tag does not match
+    decrypt_aead(cipher(aead), key, Some(nonce), aad, ciphertext, tag)
+        .map_err(|_| Error::AuthenticationFailed)
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let pkey = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &pkey).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+pub(crate) fn hkdf_sha256(
+    ikm: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    out: &mut [u8],
+) -> Result<(), Error> {
+    let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+    ctx.derive_init()?;
+    ctx.set_hkdf_md(Md::sha256())?;
+    ctx.set_hkdf_key(ikm)?;
+    ctx.set_hkdf_salt(salt)?;
+    ctx.add_hkdf_info(info)?;
+    ctx.derive(Some(out))?;
+    Ok(())
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && memcmp::eq(a, b)
+}
+
+fn cipher(aead: Aead) -> Cipher {
+    match aead {
+        Aead::Aes128Gcm => Cipher::aes_128_gcm(),
+        Aead::Aes256Gcm => Cipher::aes_256_gcm(),
+        Aead::ChaCha20Poly1305 => Cipher::chacha20_poly1305(),
+    }
+}
@@ -0,0 +1,105 @@
+use super::Aead;
+use crate::Error;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes_gcm::aead::{Aead as _, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+pub(crate) fn random_bytes(buf: &mut [u8]) -> Result<(), Error> {
+    getrandom::getrandom(buf).map_err(|_| Error::Random)
+}
+
+// AES-128-CBC with PKCS#7 padding
+pub(crate) fn aes_128_cbc_encrypt(
+    key: &[u8],
+    iv: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let cipher = Aes128CbcEnc::new_from_slices(key, iv).map_err(|_| Error::Malformed)?;
+    Ok(cipher.encrypt_padded_vec_mut::<Pkcs7>(plaintext))
+}
+
+pub(crate) fn aes_128_cbc_decrypt(
+    key: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let cipher = Aes128CbcDec::new_from_slices(key, iv).map_err(|_| Error::Malformed)?;
+    cipher
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| Error::Decryption)
+}
+
+// Returns the ciphertext followed by the tag
+pub(crate) fn aead_seal(
+    aead: Aead,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let payload = Payload {
+        msg: plaintext,
+        aad,
+    };
+    let result = match aead {
+        Aead::Aes128Gcm => new_cipher::<Aes128Gcm>(key)?.encrypt(nonce.into(), payload),
+        Aead::Aes256Gcm => new_cipher::<Aes256Gcm>(key)?.encrypt(nonce.into(), payload),
+        Aead::ChaCha20Poly1305 => {
+            new_cipher::<ChaCha20Poly1305>(key)?.encrypt(nonce.into(), payload)
+        }
+    };
+    result.map_err(|_| Error::Malformed)
+}
+
+pub(crate) fn aead_open(
+    aead: Aead,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let payload = Payload { msg: sealed, aad };
+    let result = match aead {
+        Aead::Aes128Gcm => new_cipher::<Aes128Gcm>(key)?.decrypt(nonce.into(), payload),
+        Aead::Aes256Gcm => new_cipher::<Aes256Gcm>(key)?.decrypt(nonce.into(), payload),
+        Aead::ChaCha20Poly1305 => {
+            new_cipher::<ChaCha20Poly1305>(key)?.decrypt(nonce.into(), payload)
+        }
+    };
+    result.map_err(|_| Error::AuthenticationFailed)
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub(crate) fn hkdf_sha256(
+    ikm: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    out: &mut [u8],
+) -> Result<(), Error> {
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, out)
+        .map_err(|_| Error::Malformed)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && bool::from(a.ct_eq(b))
+}
+
+fn new_cipher<C: KeyInit>(key: &[u8]) -> Result<C, Error> {
+    C::new_from_slice(key).map_err(|_| Error::Malformed)
+}
@@ -0,0 +1,122 @@
+use crate::envelope::{FLAG_DEFLATE, FLAG_PADDED, FLAG_ZSTD};
+use crate::{CipherSuite, Error, SecretBytes};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+// Limit on what a payload may decompress to, so a small crafted envelope
+// cannot expand into gigabytes
+const MAX_DECOMPRESSED_LEN: u64 = 64 * 1024 * 1024;
+
+const ZSTD_LEVEL: i32 = 19;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Zstd,
+}
+
+// How `encrypt_with_options` prepares the plaintext. The envelope header
+// records what was applied, so decryption needs no options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncryptOptions {
+    pub suite: CipherSuite,
+    // Compress the plaintext first. Skipped when it would not make the
+    // payload smaller, which is common for short inputs.
+    pub compression: Compression,
+    // Pad the (compressed) plaintext to one of a few sizes, so the envelope
+    // length reveals less about the content and how well it compressed
+    pub pad: bool,
+}
+
+// Compress and pad `plaintext` as asked, returning the result and the header
+// flags that describe it
+pub(crate) fn pack(plaintext: &[u8], options: &EncryptOptions) -> Result<(SecretBytes, u8), Error> {
+    let (mut data, mut flags) = match compress(plaintext, options.compression)? {
+        Some((compressed, flag)) if compressed.len() < plaintext.len() => (compressed, flag),
+        _ => (SecretBytes::new(plaintext.to_vec()), 0),
+    };
+
+    if options.pad {
+        data = pad(&data);
+        flags |= FLAG_PADDED;
+    }
+    Ok((data, flags))
+}
+
+// Reverse of `pack`
+pub(crate) fn unpack(data: SecretBytes, flags: u8) -> Result<SecretBytes, Error> {
+    let data = if flags & FLAG_PADDED != 0 {
+        unpad(data)?
+    } else {
+        data
+    };
+
+    if flags & FLAG_DEFLATE != 0 {
+        decompress(DeflateDecoder::new(&data[..]))
+    } else if flags & FLAG_ZSTD != 0 {
+        decompress(zstd::stream::read::Decoder::new(&data[..])?)
+    } else {
+        Ok(data)
+    }
+}
+
+fn compress(data: &[u8], compression: Compression) -> Result<Option<(SecretBytes, u8)>, Error> {
+    let (compressed, flag) = match compression {
+        Compression::None => return Ok(None),
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data)?;
+            (encoder.finish()?, FLAG_DEFLATE)
+        }
+        Compression::Zstd => (zstd::stream::encode_all(data, ZSTD_LEVEL)?, FLAG_ZSTD),
+    };
+    Ok(Some((SecretBytes::new(compressed), flag)))
+}
+
+fn decompress<R: Read>(decoder: R) -> Result<SecretBytes, Error> {
+    let mut out = SecretBytes::new(Vec::new());
+    decoder
+        .take(MAX_DECOMPRESSED_LEN + 1)
+        .read_to_end(out.as_mut_vec())
+        .map_err(|_| Error::Malformed)?;
+    if out.len() as u64 > MAX_DECOMPRESSED_LEN {
+        return Err(Error::Malformed);
+    }
+    Ok(out)
+}
+
+// 4 byte length, the data, then zeros up to the Padmé length of the whole.
+// Padmé rounds up to a size with only the top log2(log2(len)) + 1 bits set,
+// which costs at most 12% overhead and leaves O(log log len) bits of the
+// length visible.
+fn pad(data: &[u8]) -> SecretBytes {
+    let mut out = Vec::with_capacity(padme(data.len() + 4));
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+    out.resize(padme(data.len() + 4), 0);
+    SecretBytes::new(out)
+}
+
+fn unpad(data: SecretBytes) -> Result<SecretBytes, Error> {
+    if data.len() < 4 {
+        return Err(Error::Malformed);
+    }
+    let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+    if len > data.len() - 4 {
+        return Err(Error::Malformed);
+    }
+    Ok(SecretBytes::new(data[4..4 + len].to_vec()))
+}
+
+fn padme(len: usize) -> usize {
+    if len < 2 {
+        return len;
+    }
+    let e = usize::BITS - 1 - len.leading_zeros();
+    let s = u32::BITS - e.leading_zeros();
+    let mask = (1usize << (e - s)) - 1;
+    (len + mask) & !mask
+}
@@ -0,0 +1,158 @@
+use crate::color::{delta_e2000, Lab};
+use crate::{ColorAlphabet, Error};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::io::Reader as ImageReader;
+use image::RgbaImage;
+use std::io::Cursor;
+
+// How `decode_image_tolerant` reads an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodeOptions {
+    // Largest CIEDE2000 difference between a sampled color and the palette
+    // color it is read as. Beyond it decoding fails with
+    // `Error::UnreadableSymbol` rather than guess.
+    pub max_distance: f64,
+    // How many symbols the image holds. Needed for images that were resized,
+    // whose width no longer is the symbol count; `None` reads one symbol per
+    // pixel column.
+    pub symbols: Option<usize>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            max_distance: 20.0,
+            symbols: None,
+        }
+    }
+}
+
+// One symbol read by `decode_image_tolerant`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodedSymbol {
+    pub symbol: char,
+    // CIEDE2000 difference between the sampled color and the symbol's color
+    pub distance: f64,
+    // How much closer the sampled color is to this symbol than to the next
+    // nearest one, from 0 (halfway between the two) to 1 (an exact match)
+    pub confidence: f64,
+}
+
+// The symbols of an image in text order, as `decode_image_and_extract_text`
+// would return them
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedImage {
+    pub symbols: Vec<DecodedSymbol>,
+}
+
+impl DecodedImage {
+    pub fn text(&self) -> String {
+        self.symbols.iter().map(|decoded| decoded.symbol).collect()
+    }
+
+    // The confidence of the least certain symbol, 1 for an empty image
+    pub fn min_confidence(&self) -> f64 {
+        self.symbols
+            .iter()
+            .map(|decoded| decoded.confidence)
+            .fold(1.0, f64::min)
+    }
+}
+
+// Read an image that may have gone through lossy compression, color
+// management or resampling. Every symbol's color is averaged over its cell of
+// the first row and read as the nearest color of `alphabet` in CIELAB, so
+// no column is dropped the way `decode_image_with_alphabet` drops colors it
+// has no exact match for.
+pub fn decode_image_tolerant(
+    encoded_image: &str,
+    alphabet: &ColorAlphabet,
+    options: &DecodeOptions,
+) -> Result<DecodedImage, Error> {
+    let image_data = STANDARD.decode(encoded_image)?;
+    let img = ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()?
+        .decode()
+        .map_err(|_| Error::InvalidImage)?
+        .to_rgba8();
+
+    let count = options.symbols.unwrap_or(img.width() as usize);
+    if count == 0 || count > img.width() as usize || alphabet.len() < 2 {
+        return Err(Error::InvalidImage);
+    }
+    let palette: Vec<(char, Lab)> = alphabet
+        .entries()
+        .iter()
+        .map(|&(symbol, color)| (symbol, Lab::from_rgb(color)))
+        .collect();
+
+    let mut symbols = Vec::with_capacity(count);
+    for column in 0..count {
+        let lab = Lab::from_rgb(sample(&img, column, count));
+        let decoded = nearest(&palette, lab);
+
+        // `create_img` draws the last symbol in the first column
+        let position = (column + count - 1) % count;
+        if decoded.distance > options.max_distance {
+            return Err(Error::UnreadableSymbol {
+                position,
+                distance: decoded.distance,
+            });
+        }
+        symbols.push(decoded);
+    }
+    symbols.rotate_left(1);
+    Ok(DecodedImage { symbols })
+}
+
+// Average color of the middle of a symbol's cell. Scaled up `n` times, the
+// first row of the original is the top `n` rows, and the cell edges blend
+// with the neighbouring cells and the rows below.
+fn sample(img: &RgbaImage, column: usize, count: usize) -> (u8, u8, u8) {
+    let scale = img.width() as f64 / count as f64;
+    let left = column as f64 * scale;
+    let x0 = (left + scale / 4.0) as u32;
+    let x1 = ((left + scale * 3.0 / 4.0) as u32).max(x0 + 1);
+    let y1 = ((scale / 2.0) as u32).clamp(1, img.height());
+
+    let mut sum = [0u64; 3];
+    let mut pixels = 0;
+    for y in 0..y1 {
+        for x in x0..x1.min(img.width()) {
+            let [r, g, b, _] = img.get_pixel(x, y).0;
+            sum[0] += r as u64;
+            sum[1] += g as u64;
+            sum[2] += b as u64;
+            pixels += 1;
+        }
+    }
+    let [r, g, b] = sum.map(|channel| ((channel + pixels / 2) / pixels) as u8);
+    (r, g, b)
+}
+
+fn nearest(palette: &[(char, Lab)], lab: Lab) -> DecodedSymbol {
+    let mut best = (palette[0].0, f64::INFINITY);
+    let mut second = f64::INFINITY;
+    for &(symbol, color) in palette {
+        let distance = delta_e2000(lab, color);
+        if distance < best.1 {
+            second = best.1;
+            best = (symbol, distance);
+        } else if distance < second {
+            second = distance;
+        }
+    }
+    // With the sample halfway between the two nearest colors, the distances
+    // are equal and there is no telling them apart
+    let confidence = if best.1 == 0.0 {
+        1.0
+    } else {
+        (1.0 - best.1 / second).max(0.0)
+    };
+    DecodedSymbol {
+        symbol: best.0,
+        distance: best.1,
+        confidence,
+    }
+}
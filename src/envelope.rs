@@ -0,0 +1,426 @@
+use crate::kdf::{self, Kdf};
+#[cfg(feature = "openssl")]
+use crate::SigningKey;
+use crate::{CipherSuite, Error, Key};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const MAGIC: &[u8; 3] = b"CSQ";
+pub(crate) const VERSION: u8 = 1;
+
+// Key source identifiers. 1 and 2 are the passphrase KDFs, see `Kdf::id`.
+const DIRECT_ID: u8 = 0;
+const KEYRING_ID: u8 = 8;
+const X25519_ID: u8 = 16;
+const RSA_OAEP_ID: u8 = 17;
+const RECIPIENTS_ID: u8 = 32;
+
+const X25519_KEY_LEN: usize = 32;
+
+// Header flags
+pub(crate) const FLAG_STREAM: u8 = 0x01;
+pub(crate) const FLAG_DEFLATE: u8 = 0x02;
+pub(crate) const FLAG_ZSTD: u8 = 0x04;
+pub(crate) const FLAG_PADDED: u8 = 0x08;
+pub(crate) const FLAG_TIMESTAMP: u8 = 0x10;
+
+// Versioned envelope layout, integers are big endian:
+//
+//   magic       3 bytes  "CSQ"
+//   version     1 byte
+//   suite       1 byte   cipher suite identifier
+//   mac         1 byte   MAC identifier, 0 for AEAD suites
+//   key source  1 byte   how the content key is obtained, see `KeySource`
+//   flags       1 byte   0x01  chunked stream, see `stream`
+//                        0x02  plaintext is deflate compressed
+//                        0x04  plaintext is zstd compressed
+//                        0x08  plaintext is padded, see `compress::pad`
+//                        0x10  an issued at timestamp follows the key params
+//                        other bits reserved and must be 0
+//   key params           depends on the key source:
+//                          direct      nothing
+//                          keyring     1 byte length, key id
+//                          passphrase  see `kdf::write_params`
+//                          x25519      32 byte ephemeral public key
+//                          rsa-oaep    2 byte length, wrapped content key
+//                          recipients  1 byte count, then per recipient:
+//                            key source  1 byte, passphrase or public key
+//                            key id len  1 byte
+//                            key id      empty for passphrases
+//                            key params  as above
+//                            length      2 bytes
+//                            wrapped     content key sealed with AES-256-GCM
+//   issued at   8 bytes  when the envelope was sealed, in seconds since the
+//                        Unix epoch. Only present with flag 0x10.
+//   length      4 bytes  payload length
+//   payload              nonce or IV followed by the sealed data
+//   signature            optional, appended by `sign_envelope`:
+//     algorithm   1 byte   see `SigningKey`
+//     key id len  1 byte
+//     key id               see `SigningKey::key_id`
+//     length      2 bytes
+//     signature            over every byte before this length field
+//
+// Everything before the length is the header. Every suite authenticates it,
+// followed by the caller's context strings if there are any (see
+// `encode_context`), as associated data. The context is not stored, the
+// decrypting side has to supply it again. The signature is not part of what
+// the suite seals, so it can be added to or checked on an envelope without
+// the decryption key.
+//
+// Envelopes produced before this format have no magic. They are in the
+// original IV | HMAC | ciphertext layout, which only `decrypt_legacy` reads.
+pub(crate) struct Header {
+    pub suite: CipherSuite,
+    pub key_source: KeySource,
+}
+
+pub(crate) enum KeySource {
+    // The caller's key is used as is
+    Direct,
+    // A key looked up by ID in a `KeyProvider`
+    Keyring { key_id: Vec<u8> },
+    // Derived from a passphrase with the given KDF and salt
+    Passphrase(Kdf, Vec<u8>),
+    // ECDH with the recipient's X25519 key, see `PublicKey::encapsulate`
+    X25519 { ephemeral: Vec<u8> },
+    // Random content key wrapped with the recipient's RSA key
+    RsaOaep { wrapped: Vec<u8> },
+    // Random content key wrapped once per recipient, see `recipients`
+    Recipients(Vec<Stanza>),
+}
+
+// One recipient of a multi-recipient envelope. `source` yields the key that
+// unwraps `wrapped` into the content key.
+pub(crate) struct Stanza {
+    pub source: KeySource,
+    pub key_id: Vec<u8>,
+    pub wrapped: Vec<u8>,
+}
+
+impl KeySource {
+    fn id(&self) -> u8 {
+        match self {
+            KeySource::Direct => DIRECT_ID,
+            KeySource::Keyring { .. } => KEYRING_ID,
+            KeySource::Passphrase(kdf, _) => kdf.id(),
+            KeySource::X25519 { .. } => X25519_ID,
+            KeySource::RsaOaep { .. } => RSA_OAEP_ID,
+            KeySource::Recipients(_) => RECIPIENTS_ID,
+        }
+    }
+
+    fn write_params(&self, out: &mut Vec<u8>) {
+        match self {
+            KeySource::Direct => {}
+            KeySource::Keyring { key_id } => {
+                out.push(key_id.len() as u8);
+                out.extend_from_slice(key_id);
+            }
+            KeySource::Passphrase(kdf, salt) => kdf::write_params(kdf, salt, out),
+            KeySource::X25519 { ephemeral } => out.extend_from_slice(ephemeral),
+            KeySource::RsaOaep { wrapped } => {
+                out.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+                out.extend_from_slice(wrapped);
+            }
+            KeySource::Recipients(stanzas) => {
+                out.push(stanzas.len() as u8);
+                for stanza in stanzas {
+                    out.push(stanza.source.id());
+                    out.push(stanza.key_id.len() as u8);
+                    out.extend_from_slice(&stanza.key_id);
+                    stanza.source.write_params(out);
+                    out.extend_from_slice(&(stanza.wrapped.len() as u16).to_be_bytes());
+                    out.extend_from_slice(&stanza.wrapped);
+                }
+            }
+        }
+    }
+
+    fn read_params(id: u8, reader: &mut Reader) -> Result<KeySource, Error> {
+        let key_source = match id {
+            DIRECT_ID => KeySource::Direct,
+            KEYRING_ID => {
+                let len = reader.u8()?;
+                KeySource::Keyring {
+                    key_id: reader.bytes(len as usize)?.to_vec(),
+                }
+            }
+            X25519_ID => KeySource::X25519 {
+                ephemeral: reader.bytes(X25519_KEY_LEN)?.to_vec(),
+            },
+            RSA_OAEP_ID => {
+                let len = reader.u16()?;
+                KeySource::RsaOaep {
+                    wrapped: reader.bytes(len as usize)?.to_vec(),
+                }
+            }
+            RECIPIENTS_ID => {
+                let count = reader.u8()?;
+                let mut stanzas = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let id = reader.u8()?;
+                    if id == DIRECT_ID || id == KEYRING_ID || id == RECIPIENTS_ID {
+                        return Err(Error::Malformed);
+                    }
+                    let key_id_len = reader.u8()?;
+                    let key_id = reader.bytes(key_id_len as usize)?.to_vec();
+                    let source = KeySource::read_params(id, reader)?;
+                    let len = reader.u16()?;
+                    let wrapped = reader.bytes(len as usize)?.to_vec();
+                    stanzas.push(Stanza {
+                        source,
+                        key_id,
+                        wrapped,
+                    });
+                }
+                KeySource::Recipients(stanzas)
+            }
+            id => {
+                let (kdf, salt) = kdf::read_params(id, reader)?;
+                KeySource::Passphrase(kdf, salt.to_vec())
+            }
+        };
+        Ok(key_source)
+    }
+}
+
+impl Header {
+    pub(crate) fn to_bytes(&self, flags: u8) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        out.push(self.suite.id());
+        out.push(self.suite.mac_id());
+        out.push(self.key_source.id());
+        out.push(flags);
+        self.key_source.write_params(&mut out);
+        out
+    }
+}
+
+pub(crate) struct Envelope<'a> {
+    pub header: Header,
+    pub flags: u8,
+    // Seconds since the Unix epoch, `None` for envelopes sealed before
+    // timestamps were added
+    pub issued_at: Option<u64>,
+    pub header_bytes: &'a [u8],
+    pub payload: &'a [u8],
+    pub signature: Option<Signature<'a>>,
+}
+
+// Only the key ID is read without the openssl feature
+#[cfg_attr(not(feature = "openssl"), allow(dead_code))]
+pub(crate) struct Signature<'a> {
+    pub algorithm: u8,
+    pub key_id: &'a [u8],
+    // The envelope bytes the signature covers
+    pub signed: &'a [u8],
+    pub value: &'a [u8],
+}
+
+pub(crate) fn is_versioned(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// Build a complete envelope around `plaintext`
+pub(crate) fn seal(header: &Header, key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    seal_with_flags(header, 0, key, plaintext, &[])
+}
+
+// `seal` for a plaintext that was compressed or padded as `flags` say, bound
+// to the encoded `context`
+pub(crate) fn seal_with_flags(
+    header: &Header,
+    flags: u8,
+    key: &Key,
+    plaintext: &[u8],
+    context: &[u8],
+) -> Result<Vec<u8>, Error> {
+    seal_at(
+        header,
+        flags,
+        Some(unix_time(SystemTime::now())),
+        key,
+        plaintext,
+        context,
+    )
+}
+
+// `seal_with_flags` with an explicit issued at time, or none at all
+pub(crate) fn seal_at(
+    header: &Header,
+    flags: u8,
+    issued_at: Option<u64>,
+    key: &Key,
+    plaintext: &[u8],
+    context: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut out = match issued_at {
+        Some(issued_at) => {
+            let mut out = header.to_bytes(flags | FLAG_TIMESTAMP);
+            out.extend_from_slice(&issued_at.to_be_bytes());
+            out
+        }
+        None => header.to_bytes(flags),
+    };
+    let payload = header
+        .suite
+        .seal(key, plaintext, &[&out, context].concat())?;
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+pub(crate) fn parse(data: &[u8]) -> Result<Envelope<'_>, Error> {
+    let mut reader = Reader::new(data);
+    let (header, flags) = parse_header(&mut reader)?;
+    if flags & !(FLAG_DEFLATE | FLAG_ZSTD | FLAG_PADDED | FLAG_TIMESTAMP) != 0
+        || flags & (FLAG_DEFLATE | FLAG_ZSTD) == FLAG_DEFLATE | FLAG_ZSTD
+    {
+        return Err(Error::Malformed);
+    }
+    let issued_at = if flags & FLAG_TIMESTAMP != 0 {
+        Some(reader.u64()?)
+    } else {
+        None
+    };
+    let header_bytes = &data[..reader.position()];
+
+    let len = reader.u32()? as usize;
+    let payload = reader.bytes(len)?;
+
+    let signature = if reader.is_empty() {
+        None
+    } else {
+        let algorithm = reader.u8()?;
+        let key_id_len = reader.u8()?;
+        let key_id = reader.bytes(key_id_len as usize)?;
+        let signed = &data[..reader.position()];
+        let len = reader.u16()?;
+        let value = reader.bytes(len as usize)?;
+        Some(Signature {
+            algorithm,
+            key_id,
+            signed,
+            value,
+        })
+    };
+    if !reader.is_empty() {
+        return Err(Error::Malformed);
+    }
+
+    Ok(Envelope {
+        header,
+        flags,
+        issued_at,
+        header_bytes,
+        payload,
+        signature,
+    })
+}
+
+// Read the header up to the payload length, returning it with its flags
+pub(crate) fn parse_header(reader: &mut Reader) -> Result<(Header, u8), Error> {
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(Error::Malformed);
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let suite = CipherSuite::from_id(reader.u8()?)?;
+    let mac_id = reader.u8()?;
+    if mac_id != suite.mac_id() {
+        return Err(Error::UnknownAlgorithm(mac_id));
+    }
+    let key_source_id = reader.u8()?;
+    let flags = reader.u8()?;
+    let key_source = KeySource::read_params(key_source_id, reader)?;
+    Ok((Header { suite, key_source }, flags))
+}
+
+// Context strings as associated data, each behind a 4 byte length so that
+// different splits of the same characters never encode the same. No strings
+// encode to nothing, so an envelope without context opens with an empty one.
+pub(crate) fn encode_context(context: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for item in context {
+        out.extend_from_slice(&(item.len() as u32).to_be_bytes());
+        out.extend_from_slice(item.as_bytes());
+    }
+    out
+}
+
+// Times before the epoch are clamped to it
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+// Append a signature over the whole of `data`, which must be an unsigned
+// versioned envelope
+#[cfg(feature = "openssl")]
+pub(crate) fn sign(data: &[u8], signer: &SigningKey) -> Result<Vec<u8>, Error> {
+    if parse(data)?.signature.is_some() {
+        return Err(Error::AlreadySigned);
+    }
+
+    let key_id = signer.key_id()?;
+    let mut out = data.to_vec();
+    out.push(signer.algorithm_id());
+    out.push(key_id.len() as u8);
+    out.extend_from_slice(&key_id);
+
+    let signature = signer.sign(&out)?;
+    out.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+    out.extend_from_slice(&signature);
+    Ok(out)
+}
+
+// Bounds-checked cursor over envelope bytes. Every read past the end is a
+// `Malformed` error rather than a panic.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < len {
+            return Err(Error::Malformed);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    // The key does not have the length the cipher requires
+    InvalidKeyLength {
+        expected: usize,
+        actual: usize,
+    },
+    // The input is not valid base64
+    InvalidEncoding,
+    // The decoded data is too short or otherwise not an envelope
+    Malformed,
+    // The envelope was written by a newer, unsupported format version
+    UnsupportedVersion(u8),
+    // The envelope names a cipher, MAC or KDF this version does not know
+    UnknownAlgorithm(u8),
+    // A passphrase was given for a key-encrypted envelope or vice versa
+    KeyKindMismatch,
+    // The cipher suite cannot be used here, e.g. CBC for a stream
+    UnsupportedSuite,
+    // The key is not of a type this operation supports, e.g. RSA under 2048
+    // bits for encryption or a curve other than P-256 for signing
+    UnsupportedKeyType,
+    // No recipients, more than 255, or more than 8 passphrase recipients
+    InvalidRecipients,
+    // The key provider has no key with this ID
+    UnknownKeyId(String),
+    // A keyring file or environment variable could not be parsed, or a key
+    // ID is empty or longer than 255 bytes
+    InvalidKeyring,
+    // The private key is not among the envelope's recipients
+    NotARecipient,
+    // A secret sharing threshold below 2 or above the number of shares
+    InvalidThreshold,
+    // Fewer distinct shares than the threshold
+    NotEnoughShares,
+    // The shares come from different splits, or one was altered
+    InconsistentShares,
+    // The envelope carries no signature
+    MissingSignature,
+    // The envelope is already signed
+    AlreadySigned,
+    // The signature does not verify or was made by a different key
+    InvalidSignature,
+    // No colorsquare could be read from the image
+    InvalidImage,
+    // The color sampled for the symbol at `position` is further than the
+    // allowed distance from every palette color
+    UnreadableSymbol {
+        position: usize,
+        distance: f64,
+    },
+    // A color alphabet repeats a symbol or a color, or a palette file could
+    // not be parsed
+    InvalidAlphabet,
+    // Two colors of a palette are closer in CIELAB than its minimum distance
+    ColorsTooClose {
+        first: char,
+        second: char,
+        distance: f64,
+    },
+    // The passphrase KDF parameters are out of range
+    InvalidKdfParameters,
+    // The MAC or AEAD tag did not verify: wrong key, or the envelope was
+    // tampered with
+    AuthenticationFailed,
+    // The ciphertext authenticated but could not be decrypted
+    Decryption,
+    // The plaintext is not UTF-8 and was asked for as a string
+    InvalidUtf8,
+    // The envelope is older than the maximum age, was issued further in the
+    // future than the clock skew allows, or has no timestamp to check
+    Expired,
+    // The system random number generator failed
+    Random,
+    // An error reported by OpenSSL
+    #[cfg(feature = "openssl")]
+    Crypto(openssl::error::ErrorStack),
+    // Reading a keyring or other input failed
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidKeyLength { expected, actual } => {
+                write!(
+                    f,
+                    "invalid key length: expected {} bytes, got {}",
+                    expected, actual
+                )
+            }
+            Error::InvalidEncoding => write!(f, "input is not valid base64"),
+            Error::Malformed => write!(f, "malformed envelope"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported envelope version {}", version)
+            }
+            Error::UnknownAlgorithm(id) => write!(f, "unknown algorithm identifier {}", id),
+            Error::KeyKindMismatch => {
+                write!(f, "envelope was not encrypted with this kind of key")
+            }
+            Error::UnsupportedSuite => write!(f, "cipher suite not supported here"),
+            Error::UnsupportedKeyType => write!(f, "unsupported public key type"),
+            Error::InvalidRecipients => write!(
+                f,
+                "between 1 and 255 recipients, at most 8 with a passphrase, are required"
+            ),
+            Error::UnknownKeyId(key_id) => write!(f, "unknown key id {:?}", key_id),
+            Error::InvalidKeyring => write!(f, "invalid keyring"),
+            Error::NotARecipient => write!(f, "key is not a recipient of this envelope"),
+            Error::InvalidThreshold => {
+                write!(
+                    f,
+                    "threshold must be at least 2 and at most the share count"
+                )
+            }
+            Error::NotEnoughShares => write!(f, "not enough shares to recover the secret"),
+            Error::InconsistentShares => write!(f, "shares do not belong together"),
+            Error::MissingSignature => write!(f, "envelope is not signed"),
+            Error::AlreadySigned => write!(f, "envelope is already signed"),
+            Error::InvalidSignature => write!(f, "invalid signature"),
+            Error::InvalidImage => write!(f, "no colorsquare found in image"),
+            Error::UnreadableSymbol { position, distance } => write!(
+                f,
+                "symbol {} is unreadable, {:.2} from the nearest color",
+                position, distance
+            ),
+            Error::InvalidAlphabet => write!(f, "invalid color alphabet"),
+            Error::ColorsTooClose {
+                first,
+                second,
+                distance,
+            } => write!(
+                f,
+                "colors of {:?} and {:?} are too close, {:.2} apart",
+                first, second, distance
+            ),
+            Error::InvalidKdfParameters => write!(f, "invalid key derivation parameters"),
+            Error::AuthenticationFailed => {
+                write!(f, "authentication failed, wrong key or tampered data")
+            }
+            Error::Decryption => write!(f, "decryption failed"),
+            Error::InvalidUtf8 => write!(f, "decrypted data is not valid UTF-8"),
+            Error::Expired => write!(f, "envelope has expired or has no valid timestamp"),
+            Error::Random => write!(f, "random number generator failed"),
+            #[cfg(feature = "openssl")]
+            Error::Crypto(err) => write!(f, "openssl error: {}", err),
+            Error::Io(err) => write!(f, "i/o error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "openssl")]
+            Error::Crypto(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        Error::Crypto(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        // Our own errors come back wrapped after passing through the `Read`
+        // and `Write` impls of the stream adapters
+        match err.get_ref().map(|inner| inner.is::<Error>()) {
+            Some(true) => *err.into_inner().unwrap().downcast::<Error>().unwrap(),
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(_: base64::DecodeError) -> Self {
+        Error::InvalidEncoding
+    }
+}
@@ -0,0 +1,124 @@
+use crate::backend;
+use crate::envelope::{unix_time, Reader};
+use crate::suite::verify_hmac;
+use crate::{create_img, decode_image_and_extract_text};
+use crate::{Error, Key, SecretBytes, SecretString};
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use std::time::{Duration, SystemTime};
+
+const VERSION: u8 = 0x80;
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 16;
+const HMAC_LEN: usize = 32;
+const BLOCK_LEN: usize = 16;
+
+// How far in the future a token's timestamp may be, fixed by the spec
+const MAX_CLOCK_SKEW: u64 = 60;
+
+// Fernet token layout (https://github.com/fernet/spec), URL-safe base64 with
+// padding:
+//
+//   version     1 byte   0x80
+//   timestamp   8 bytes  seconds since the Unix epoch, big endian
+//   iv         16 bytes
+//   ciphertext           AES-128-CBC with PKCS#7 padding
+//   hmac       32 bytes  HMAC-SHA256 over everything before it
+//
+// The 32 byte key is the HMAC key followed by the AES key. Tokens are
+// interchangeable with other Fernet implementations, such as Python's
+// `cryptography.fernet`.
+
+// Read a key in the usual Fernet form, 32 bytes of URL-safe base64
+pub fn decode_fernet_key(encoded: &str) -> Result<Key, Error> {
+    let key = Key::from_vec(URL_SAFE.decode(encoded.trim())?);
+    key.check_len(KEY_LEN)?;
+    Ok(key)
+}
+
+pub fn encode_fernet_key(key: &Key) -> Result<SecretString, Error> {
+    key.check_len(KEY_LEN)?;
+    Ok(SecretString::new(URL_SAFE.encode(key.as_bytes())))
+}
+
+pub fn encrypt_fernet(input: &[u8], key: &Key) -> Result<String, Error> {
+    let mut iv = [0; IV_LEN];
+    backend::random_bytes(&mut iv)?;
+    encrypt_fernet_with_iv(input, key, SystemTime::now(), &iv)
+}
+
+// Only for reproducing the spec's test vectors. Never reuse an IV.
+#[doc(hidden)]
+pub fn encrypt_fernet_with_iv(
+    input: &[u8],
+    key: &Key,
+    now: SystemTime,
+    iv: &[u8; IV_LEN],
+) -> Result<String, Error> {
+    key.check_len(KEY_LEN)?;
+    let (signing_key, encryption_key) = key.as_bytes().split_at(KEY_LEN / 2);
+
+    let mut token = vec![VERSION];
+    token.extend_from_slice(&unix_time(now).to_be_bytes());
+    token.extend_from_slice(iv);
+    token.extend_from_slice(&backend::aes_128_cbc_encrypt(encryption_key, iv, input)?);
+    let hmac = backend::hmac_sha256(signing_key, &token);
+    token.extend_from_slice(&hmac);
+
+    Ok(URL_SAFE.encode(token))
+}
+
+// Decrypt a Fernet token. With a `ttl`, tokens older than that fail with
+// `Error::Expired`. Tokens more than 60 seconds in the future always do.
+pub fn decrypt_fernet(token: &str, key: &Key, ttl: Option<Duration>) -> Result<SecretBytes, Error> {
+    decrypt_fernet_at(token, key, ttl, SystemTime::now())
+}
+
+// `decrypt_fernet` as if the current time were `now`
+pub fn decrypt_fernet_at(
+    token: &str,
+    key: &Key,
+    ttl: Option<Duration>,
+    now: SystemTime,
+) -> Result<SecretBytes, Error> {
+    key.check_len(KEY_LEN)?;
+    let (signing_key, encryption_key) = key.as_bytes().split_at(KEY_LEN / 2);
+
+    let data = URL_SAFE.decode(token)?;
+    if data.len() < 1 + 8 + IV_LEN + BLOCK_LEN + HMAC_LEN {
+        return Err(Error::Malformed);
+    }
+    let (signed, hmac) = data.split_at(data.len() - HMAC_LEN);
+    let mut reader = Reader::new(signed);
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let timestamp = reader.u64()?;
+    let iv = reader.bytes(IV_LEN)?;
+    let ciphertext = reader.bytes(signed.len() - reader.position())?;
+
+    verify_hmac(signed, signing_key, hmac)?;
+
+    // The spec applies the TTL without skew and the skew only to the future
+    let now = unix_time(now);
+    let expired = ttl.is_some_and(|ttl| timestamp.saturating_add(ttl.as_secs()) < now);
+    if expired || timestamp > now.saturating_add(MAX_CLOCK_SKEW) {
+        return Err(Error::Expired);
+    }
+
+    let plaintext = backend::aes_128_cbc_decrypt(encryption_key, iv, ciphertext)?;
+    Ok(SecretBytes::new(plaintext))
+}
+
+// Render a Fernet token with `create_img`. The image alphabet is standard
+// base64, so '-' and '_' are drawn as '+' and '/'.
+pub fn create_fernet_img(token: &str, watermark: &str) -> Option<String> {
+    create_img(&token.replace('-', "+").replace('_', "/"), watermark)
+}
+
+// Reverse of `create_fernet_img`, returning the token in its URL-safe form
+pub fn extract_fernet_token(encoded_image: &str) -> Result<String, Error> {
+    let extracted_text = decode_image_and_extract_text(encoded_image).ok_or(Error::InvalidImage)?;
+    Ok(extracted_text.replace('+', "-").replace('/', "_"))
+}
@@ -0,0 +1,8 @@
+use crate::{backend, Error, Key};
+
+// HKDF-SHA256 (RFC 5869) extract-and-expand of `ikm` into `len` bytes
+pub(crate) fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], len: usize) -> Result<Key, Error> {
+    let mut out = vec![0; len];
+    backend::hkdf_sha256(ikm, salt, info, &mut out)?;
+    Ok(Key::from_vec(out))
+}
@@ -0,0 +1,149 @@
+use crate::envelope::Reader;
+use crate::{backend, Error, Key};
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::Sha256;
+
+const SALT_LEN: usize = 16;
+
+const ARGON2ID_ID: u8 = 1;
+const PBKDF2_SHA256_ID: u8 = 2;
+
+// Upper bounds for cost parameters read back from an envelope, so a crafted
+// image cannot make us allocate gigabytes or spin for hours. Encryption checks
+// them too, or it would write envelopes that can never be decrypted.
+const MAX_ARGON2_M_COST: u32 = 1 << 20; // 1 GiB
+const MAX_ARGON2_T_COST: u32 = 64;
+const MAX_ARGON2_P_COST: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+// How a key is derived from a passphrase. The choice and its cost parameters
+// are stored next to the salt in the envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    // Memory-hard Argon2id (RFC 9106). m_cost is in KiB.
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+    // PBKDF2-HMAC-SHA256, for platforms where Argon2id is too expensive
+    Pbkdf2Sha256 {
+        rounds: u32,
+    },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        // OWASP recommended minimum for Argon2id
+        Kdf::Argon2id {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+impl Kdf {
+    pub fn pbkdf2_default() -> Kdf {
+        Kdf::Pbkdf2Sha256 { rounds: 600_000 }
+    }
+
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Kdf::Argon2id { .. } => ARGON2ID_ID,
+            Kdf::Pbkdf2Sha256 { .. } => PBKDF2_SHA256_ID,
+        }
+    }
+
+    pub(crate) fn derive(&self, passphrase: &str, salt: &[u8], len: usize) -> Result<Key, Error> {
+        let mut out = vec![0; len];
+        match *self {
+            Kdf::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let params = Params::new(m_cost, t_cost, p_cost, Some(len))
+                    .map_err(|_| Error::InvalidKdfParameters)?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), salt, &mut out)
+                    .map_err(|_| Error::InvalidKdfParameters)?;
+            }
+            Kdf::Pbkdf2Sha256 { rounds } => {
+                if rounds == 0 {
+                    return Err(Error::InvalidKdfParameters);
+                }
+                pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut out);
+            }
+        }
+        Ok(Key::from_vec(out))
+    }
+
+    pub(crate) fn check_bounds(&self) -> Result<(), Error> {
+        let ok = match *self {
+            Kdf::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                m_cost <= MAX_ARGON2_M_COST
+                    && t_cost <= MAX_ARGON2_T_COST
+                    && p_cost <= MAX_ARGON2_P_COST
+            }
+            Kdf::Pbkdf2Sha256 { rounds } => rounds <= MAX_PBKDF2_ROUNDS,
+        };
+        if !ok {
+            return Err(Error::InvalidKdfParameters);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn generate_salt() -> Result<Vec<u8>, Error> {
+    let mut salt = vec![0; SALT_LEN];
+    backend::random_bytes(&mut salt)?;
+    Ok(salt)
+}
+
+// Serialize the KDF cost parameters and the salt:
+// parameters (big endian u32s) | salt length (1 byte) | salt
+// The KDF identifier itself is part of the envelope header.
+pub(crate) fn write_params(kdf: &Kdf, salt: &[u8], out: &mut Vec<u8>) {
+    match *kdf {
+        Kdf::Argon2id {
+            m_cost,
+            t_cost,
+            p_cost,
+        } => {
+            out.extend_from_slice(&m_cost.to_be_bytes());
+            out.extend_from_slice(&t_cost.to_be_bytes());
+            out.extend_from_slice(&p_cost.to_be_bytes());
+        }
+        Kdf::Pbkdf2Sha256 { rounds } => {
+            out.extend_from_slice(&rounds.to_be_bytes());
+        }
+    }
+    out.push(salt.len() as u8);
+    out.extend_from_slice(salt);
+}
+
+// Parse what `write_params` wrote for the KDF identified by `id`. Returns the
+// KDF and the salt.
+pub(crate) fn read_params<'a>(id: u8, reader: &mut Reader<'a>) -> Result<(Kdf, &'a [u8]), Error> {
+    let kdf = match id {
+        ARGON2ID_ID => Kdf::Argon2id {
+            m_cost: reader.u32()?,
+            t_cost: reader.u32()?,
+            p_cost: reader.u32()?,
+        },
+        PBKDF2_SHA256_ID => Kdf::Pbkdf2Sha256 {
+            rounds: reader.u32()?,
+        },
+        _ => return Err(Error::UnknownAlgorithm(id)),
+    };
+    kdf.check_bounds()?;
+
+    let salt_len = reader.u8()?;
+    let salt = reader.bytes(salt_len as usize)?;
+    Ok((kdf, salt))
+}
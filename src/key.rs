@@ -0,0 +1,73 @@
+use crate::{backend, Error};
+use std::fmt;
+use zeroize::Zeroize;
+
+// The key that shipped hardcoded in `encrypts`/`decrypts`, NUL-padded to 16
+// bytes. Only the legacy shims use it.
+const LEGACY_KEY: &[u8; 16] = b"welovenfts\0\0\0\0\0\0";
+
+// Key material, overwritten with zeros on drop and redacted from `Debug`
+#[derive(Clone)]
+pub struct Key {
+    bytes: Vec<u8>,
+}
+
+impl Key {
+    // Wrap caller-supplied key material. The length is checked against the
+    // cipher when the key is used; short keys are rejected, never padded.
+    pub fn new(bytes: &[u8]) -> Key {
+        Key {
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    // Generate a random key of `len` bytes from the system CSPRNG
+    pub fn generate(len: usize) -> Result<Key, Error> {
+        let mut bytes = vec![0; len];
+        backend::random_bytes(&mut bytes)?;
+        Ok(Key { bytes })
+    }
+
+    // Take ownership of freshly derived key material without copying it
+    pub(crate) fn from_vec(bytes: Vec<u8>) -> Key {
+        Key { bytes }
+    }
+
+    pub(crate) fn legacy() -> Key {
+        Key::new(LEGACY_KEY)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(crate) fn check_len(&self, expected: usize) -> Result<(), Error> {
+        if self.bytes.len() != expected {
+            return Err(Error::InvalidKeyLength {
+                expected,
+                actual: self.bytes.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key([REDACTED; {} bytes])", self.bytes.len())
+    }
+}
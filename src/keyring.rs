@@ -0,0 +1,170 @@
+use crate::{Error, Key, SecretString};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const MAX_KEY_ID_LEN: usize = u8::MAX as usize;
+
+// Where `encrypt_with_provider` and `decrypt_with_provider` get their keys.
+// Every envelope records the ID of the key it was sealed with, so keys can be
+// rotated: new envelopes use the current key while older ones keep opening
+// with the retired key they name.
+pub trait KeyProvider {
+    // ID of the key new envelopes are encrypted with
+    fn current_key_id(&self) -> Result<String, Error>;
+
+    // Look up a current or retired key, `Error::UnknownKeyId` if there is none
+    fn key(&self, key_id: &str) -> Result<Key, Error>;
+}
+
+// Keys held in memory, e.g. fetched from a secret store at startup
+#[derive(Clone)]
+pub struct InMemoryKeyring {
+    current: String,
+    keys: HashMap<String, Key>,
+}
+
+impl InMemoryKeyring {
+    pub fn new(current_key_id: &str, current_key: Key) -> Result<InMemoryKeyring, Error> {
+        let mut keyring = InMemoryKeyring {
+            current: current_key_id.to_string(),
+            keys: HashMap::new(),
+        };
+        keyring.add(current_key_id, current_key)?;
+        Ok(keyring)
+    }
+
+    // Add a key, replacing any key with the same ID. Does not change the
+    // current key.
+    pub fn add(&mut self, key_id: &str, key: Key) -> Result<(), Error> {
+        check_key_id(key_id)?;
+        self.keys.insert(key_id.to_string(), key);
+        Ok(())
+    }
+
+    // Make an already added key the current one. The previous current key
+    // stays available for decryption.
+    pub fn set_current(&mut self, key_id: &str) -> Result<(), Error> {
+        if !self.keys.contains_key(key_id) {
+            return Err(Error::UnknownKeyId(key_id.to_string()));
+        }
+        self.current = key_id.to_string();
+        Ok(())
+    }
+}
+
+impl KeyProvider for InMemoryKeyring {
+    fn current_key_id(&self) -> Result<String, Error> {
+        Ok(self.current.clone())
+    }
+
+    fn key(&self, key_id: &str) -> Result<Key, Error> {
+        self.keys
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| Error::UnknownKeyId(key_id.to_string()))
+    }
+}
+
+// Keys read from a text file, one `<key id> <base64 key>` per line. Blank
+// lines and lines starting with `#` are skipped. The last key in the file is
+// the current one, so rotating is appending a line.
+pub struct FileKeyring {
+    keyring: InMemoryKeyring,
+}
+
+impl FileKeyring {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileKeyring, Error> {
+        let contents = SecretString::new(fs::read_to_string(path)?);
+        FileKeyring::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<FileKeyring, Error> {
+        let mut keyring: Option<InMemoryKeyring> = None;
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let (key_id, key) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(key_id), Some(key), None) => (key_id, decode_key(key)?),
+                _ => return Err(Error::InvalidKeyring),
+            };
+
+            match keyring.as_mut() {
+                Some(keyring) => {
+                    keyring.add(key_id, key)?;
+                    keyring.set_current(key_id)?;
+                }
+                None => keyring = Some(InMemoryKeyring::new(key_id, key)?),
+            }
+        }
+
+        Ok(FileKeyring {
+            keyring: keyring.ok_or(Error::InvalidKeyring)?,
+        })
+    }
+}
+
+impl KeyProvider for FileKeyring {
+    fn current_key_id(&self) -> Result<String, Error> {
+        self.keyring.current_key_id()
+    }
+
+    fn key(&self, key_id: &str) -> Result<Key, Error> {
+        self.keyring.key(key_id)
+    }
+}
+
+// Keys read from environment variables when they are needed: with prefix
+// `APP`, `APP_CURRENT_KEY_ID` names the current key and `APP_KEY_<key id>`
+// holds each key, base64 encoded
+pub struct EnvKeyring {
+    prefix: String,
+}
+
+impl EnvKeyring {
+    pub fn new(prefix: &str) -> EnvKeyring {
+        EnvKeyring {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<String> {
+        env::var(format!("{}_{}", self.prefix, name)).ok()
+    }
+}
+
+impl KeyProvider for EnvKeyring {
+    fn current_key_id(&self) -> Result<String, Error> {
+        let key_id = self.var("CURRENT_KEY_ID").ok_or(Error::InvalidKeyring)?;
+        check_key_id(&key_id)?;
+        Ok(key_id)
+    }
+
+    fn key(&self, key_id: &str) -> Result<Key, Error> {
+        let key = self
+            .var(&format!("KEY_{}", key_id))
+            .ok_or_else(|| Error::UnknownKeyId(key_id.to_string()))?;
+        decode_key(&SecretString::new(key))
+    }
+}
+
+// Key IDs are stored in the envelope behind a one byte length
+pub(crate) fn check_key_id(key_id: &str) -> Result<(), Error> {
+    if key_id.is_empty() || key_id.len() > MAX_KEY_ID_LEN {
+        return Err(Error::InvalidKeyring);
+    }
+    Ok(())
+}
+
+fn decode_key(encoded: &str) -> Result<Key, Error> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| Error::InvalidKeyring)?;
+    Ok(Key::from_vec(bytes))
+}
@@ -0,0 +1,81 @@
+use crate::backend;
+use crate::envelope;
+use crate::suite::verify_hmac;
+use crate::{create_img, decode_image_and_extract_text, encrypt_with};
+use crate::{Error, Key, SecretBytes, SecretString};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+const KEY_LEN: usize = 16;
+const IV_LEN: usize = 16;
+const HMAC_LEN: usize = 32;
+
+// The original `encrypts` used the base64 encoding of the first 10 plaintext
+// bytes, 16 characters, as the IV
+const IV_PREFIX_LEN: usize = 10;
+
+// Opens the layout `encrypts` produced before the versioned envelope:
+// IV | HMAC | ciphertext, with the same AES-128-CBC key used for the HMAC and
+// the HMAC covering the ciphertext only. Nothing writes this layout anymore.
+pub(crate) fn open(key: &Key, data: &[u8]) -> Result<Vec<u8>, Error> {
+    key.check_len(KEY_LEN)?;
+    if data.len() < IV_LEN + HMAC_LEN {
+        return Err(Error::Malformed);
+    }
+
+    // Extract the IV, HMAC, and ciphertext from the result
+    let (iv, rest) = data.split_at(IV_LEN);
+    let (hmac, ciphertext) = rest.split_at(HMAC_LEN);
+
+    // Verify the HMAC
+    verify_hmac(ciphertext, key.as_bytes(), hmac)?;
+
+    backend::aes_128_cbc_decrypt(key.as_bytes(), iv, ciphertext)
+}
+
+// Decrypts the output of the original `encrypts` with the hardcoded key it
+// used. Fails with `Error::Malformed` for anything not in that layout.
+pub fn decrypt_legacy(encoded_result: &str) -> Result<SecretString, Error> {
+    let data = STANDARD.decode(encoded_result)?;
+    if envelope::is_versioned(&data) || data.len() < IV_LEN + HMAC_LEN {
+        return Err(Error::Malformed);
+    }
+    let prefix = STANDARD
+        .decode(&data[..IV_LEN])
+        .map_err(|_| Error::Malformed)?;
+    if prefix.len() != IV_PREFIX_LEN {
+        return Err(Error::Malformed);
+    }
+
+    let plaintext = SecretBytes::new(open(&Key::legacy(), &data)?);
+    if !plaintext.starts_with(&prefix) {
+        return Err(Error::Malformed);
+    }
+    plaintext.into_string()
+}
+
+// `decrypt_legacy` for an image made with `create_img` from the output of
+// the original `encrypts`
+pub fn decrypt_legacy_image(encoded_image: &str) -> Result<SecretString, Error> {
+    let extracted_text = decode_image_and_extract_text(encoded_image).ok_or(Error::InvalidImage)?;
+
+    // Older versions read the color of '+' as '*'. '*' is not in the base64
+    // alphabet, so wherever it turns up it can only have been a '+'.
+    decrypt_legacy(&extracted_text.replace('*', "+"))
+}
+
+// Decrypts the output of the original `encrypts` and encrypts the payload
+// again in the versioned format with `key`
+pub fn migrate_legacy(encoded_result: &str, key: &Key) -> Result<String, Error> {
+    encrypt_with(&decrypt_legacy(encoded_result)?, key)
+}
+
+// `migrate_legacy` for images, returning a new image with `watermark`
+pub fn migrate_legacy_image(
+    encoded_image: &str,
+    key: &Key,
+    watermark: &str,
+) -> Result<String, Error> {
+    let encrypted = encrypt_with(&decrypt_legacy_image(encoded_image)?, key)?;
+    create_img(&encrypted, watermark).ok_or(Error::InvalidImage)
+}
@@ -1,200 +1,226 @@
-use base64::encode;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use image::codecs::png::PngEncoder;
 use image::io::Reader as ImageReader;
-use image::{ColorType, DynamicImage, Rgba, RgbaImage};
-use openssl::hash::{Hasher, MessageDigest};
-use openssl::pkey::PKey;
-use openssl::sign::Verifier;
-use openssl::symm::{decrypt, encrypt, Cipher};
-use std::collections::HashMap;
+use image::{ColorType, DynamicImage, ImageEncoder, Rgba, RgbaImage};
 use std::io::Cursor;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+mod alphabet;
+mod backend;
+mod color;
+mod compress;
+mod decode;
+mod envelope;
+mod error;
+mod fernet;
+mod hkdf;
+mod kdf;
+mod key;
+mod keyring;
+mod legacy;
+mod palette;
+#[cfg(feature = "openssl")]
+mod pubkey;
+mod recipients;
+mod secret;
+mod shamir;
+#[cfg(feature = "openssl")]
+mod sign;
+mod stream;
+mod suite;
+
+pub use alphabet::ColorAlphabet;
+pub use compress::{Compression, EncryptOptions};
+pub use decode::{decode_image_tolerant, DecodeOptions, DecodedImage, DecodedSymbol};
+pub use error::Error;
+pub use fernet::{
+    create_fernet_img, decode_fernet_key, decrypt_fernet, decrypt_fernet_at, encode_fernet_key,
+    encrypt_fernet, encrypt_fernet_with_iv, extract_fernet_token,
+};
+pub use kdf::Kdf;
+pub use key::Key;
+pub use keyring::{EnvKeyring, FileKeyring, InMemoryKeyring, KeyProvider};
+pub use legacy::{decrypt_legacy, decrypt_legacy_image, migrate_legacy, migrate_legacy_image};
+pub use palette::{generate_palette, GeneratedPalette, PaletteConstraints};
+#[cfg(feature = "openssl")]
+pub use pubkey::{PrivateKey, PublicKey};
+pub use recipients::{Recipient, RecipientInfo, RecipientKind};
+pub use secret::{SecretBytes, SecretString};
+pub use shamir::{combine_images, combine_shares, split_secret, split_to_images};
+#[cfg(feature = "openssl")]
+pub use sign::{SigningKey, VerifyingKey};
+pub use stream::{decrypt_stream, encrypt_stream, StreamDecryptor, StreamEncryptor};
+pub use suite::CipherSuite;
+
+use envelope::{Envelope, Header, KeySource};
 
 pub fn get_color(char: char) -> Option<(u8, u8, u8)> {
-    match char {
-        'a' => Some((204, 180, 194)),
-        'A' => Some((255, 255, 255)),
-        'b' => Some((197, 186, 201)),
-        'B' => Some((221, 206, 212)),
-        'c' => Some((181, 185, 193)),
-        'C' => Some((184, 201, 223)),
-        'd' => Some((224, 218, 192)),
-        'D' => Some((185, 191, 195)),
-        'e' => Some((181, 197, 198)),
-        'E' => Some((193, 206, 255)),
-        'f' => Some((252, 193, 211)),
-        'F' => Some((183, 192, 229)),
-        'g' => Some((180, 191, 192)),
-        'G' => Some((187, 219, 189)),
-        'h' => Some((195, 187, 234)),
-        'H' => Some((182, 216, 189)),
-        'i' => Some((197, 183, 248)),
-        'I' => Some((200, 182, 204)),
-        'j' => Some((255, 235, 196)),
-        'J' => Some((194, 186, 228)),
-        'k' => Some((199, 238, 239)),
-        'K' => Some((208, 247, 234)),
-        'l' => Some((244, 214, 189)),
-        'L' => Some((187, 243, 239)),
-        'm' => Some((188, 231, 238)),
-        'M' => Some((187, 197, 227)),
-        'n' => Some((186, 240, 191)),
-        'N' => Some((187, 198, 206)),
-        'o' => Some((205, 193, 184)),
-        'O' => Some((191, 187, 197)),
-        'p' => Some((194, 200, 206)),
-        'P' => Some((195, 183, 229)),
-        'q' => Some((182, 219, 196)),
-        'Q' => Some((238, 216, 184)),
-        'r' => Some((199, 181, 208)),
-        'R' => Some((239, 231, 198)),
-        's' => Some((189, 188, 230)),
-        'S' => Some((242, 192, 230)),
-        't' => Some((199, 199, 199)),
-        'T' => Some((188, 190, 230)),
-        'u' => Some((230, 180, 253)),
-        'U' => Some((241, 247, 247)),
-        'v' => Some((242, 190, 199)),
-        'V' => Some((230, 247, 234)),
-        'w' => Some((197, 186, 249)),
-        'W' => Some((194, 247, 249)),
-        'x' => Some((242, 182, 246)),
-        'X' => Some((188, 222, 193)),
-        'y' => Some((188, 194, 183)),
-        'Y' => Some((197, 195, 197)),
-        'z' => Some((187, 249, 240)),
-        'Z' => Some((233, 231, 242)),
-        '0' => Some((195, 184, 218)),
-        '1' => Some((232, 180, 196)),
-        '2' => Some((191, 193, 196)),
-        '3' => Some((185, 186, 186)),
-        '4' => Some((191, 247, 180)),
-        '5' => Some((187, 199, 248)),
-        '6' => Some((248, 198, 184)),
-        '7' => Some((243, 195, 184)),
-        '8' => Some((232, 192, 208)),
-        '9' => Some((239, 197, 183)),
-        '/' => Some((199, 187, 241)),
-        '+' => Some((195, 216, 223)),
-        '=' => Some((193, 211, 184)),
-        _ => None,
-    }
+    ColorAlphabet::standard().color(char)
 }
+
 pub fn numbers_to_letter(r: u8, g: u8, b: u8) -> Option<char> {
-    let color_map: HashMap<(u8, u8, u8), char> = [
-        ((204, 180, 194), 'a'),
-        ((255, 255, 255), 'A'),
-        ((197, 186, 201), 'b'),
-        ((221, 206, 212), 'B'),
-        ((181, 185, 193), 'c'),
-        ((184, 201, 223), 'C'),
-        ((224, 218, 192), 'd'),
-        ((185, 191, 195), 'D'),
-        ((181, 197, 198), 'e'),
-        ((193, 206, 255), 'E'),
-        ((252, 193, 211), 'f'),
-        ((183, 192, 229), 'F'),
-        ((180, 191, 192), 'g'),
-        ((187, 219, 189), 'G'),
-        ((195, 187, 234), 'h'),
-        ((182, 216, 189), 'H'),
-        ((197, 183, 248), 'i'),
-        ((200, 182, 204), 'I'),
-        ((255, 235, 196), 'j'),
-        ((194, 186, 228), 'J'),
-        ((199, 238, 239), 'k'),
-        ((208, 247, 234), 'K'),
-        ((244, 214, 189), 'l'),
-        ((187, 243, 239), 'L'),
-        ((188, 231, 238), 'm'),
-        ((187, 197, 227), 'M'),
-        ((186, 240, 191), 'n'),
-        ((187, 198, 206), 'N'),
-        ((205, 193, 184), 'o'),
-        ((191, 187, 197), 'O'),
-        ((194, 200, 206), 'p'),
-        ((195, 183, 229), 'P'),
-        ((182, 219, 196), 'q'),
-        ((238, 216, 184), 'Q'),
-        ((199, 181, 208), 'r'),
-        ((239, 231, 198), 'R'),
-        ((189, 188, 230), 's'),
-        ((242, 192, 230), 'S'),
-        ((199, 199, 199), 't'),
-        ((188, 190, 230), 'T'),
-        ((230, 180, 253), 'u'),
-        ((241, 247, 247), 'U'),
-        ((242, 190, 199), 'v'),
-        ((230, 247, 234), 'V'),
-        ((197, 186, 249), 'w'),
-        ((194, 247, 249), 'W'),
-        ((242, 182, 246), 'x'),
-        ((188, 222, 193), 'X'),
-        ((188, 194, 183), 'y'),
-        ((197, 195, 197), 'Y'),
-        ((187, 249, 240), 'z'),
-        ((233, 231, 242), 'Z'),
-        ((195, 184, 218), '0'),
-        ((232, 180, 196), '1'),
-        ((191, 193, 196), '2'),
-        ((185, 186, 186), '3'),
-        ((191, 247, 180), '4'),
-        ((187, 199, 248), '5'),
-        ((248, 198, 184), '6'),
-        ((243, 195, 184), '7'),
-        ((232, 192, 208), '8'),
-        ((239, 197, 183), '9'),
-        ((199, 187, 241), '/'),
-        ((195, 216, 223), '*'),
-        ((193, 211, 184), '='),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    match color_map.get(&(r, g, b)) {
-        Some(&letter) => Some(letter),
-        None => None,
-    }
+    ColorAlphabet::standard().symbol((r, g, b))
 }
+
+// Legacy shim: encrypts with the key that is hardcoded in this crate, so anyone
+// can decrypt the result. Use `encrypt_with` and your own `Key` instead.
+#[deprecated(note = "uses a hardcoded key, use `encrypt_with` with your own `Key`")]
 pub fn encrypts(input: &str) -> String {
-    let cipher = Cipher::aes_128_cbc();
-    let key = "welovenfts";
-    // Take the first 10 bytes of the input as the IV
-    let iv_bytes = &input.to_string()[..10];
-
-    // Base64 encode the IV
-    let iv = base64::encode(iv_bytes);
-
-    // Pad the key with NUL bytes if it's shorter than 16 bytes
-    let mut padded_key = key.as_bytes().to_vec();
-    while padded_key.len() < 16 {
-        padded_key.push(b'\0');
-    }
+    encrypt_with(input, &Key::legacy()).unwrap()
+}
 
-    // Encrypt the input
-    let ciphertext = encrypt(cipher, &padded_key, Some(iv.as_bytes()), input.as_bytes()).unwrap();
+pub fn encrypt_with(input: &str, key: &Key) -> Result<String, Error> {
+    Ok(STANDARD.encode(encrypt_bytes(input.as_bytes(), key)?))
+}
 
-    let hmac = calculate_hmac(&ciphertext, &padded_key);
+// Encrypts arbitrary bytes, returning the raw envelope without base64
+pub fn encrypt_bytes(input: &[u8], key: &Key) -> Result<Vec<u8>, Error> {
+    encrypt_bytes_with_options(input, key, &EncryptOptions::default())
+}
 
-    // Combine IV, HMAC, and ciphertext into the final result
-    let mut result = iv.into_bytes();
-    result.extend_from_slice(&hmac);
-    result.extend_from_slice(&ciphertext);
+// Same as `encrypt_with` but with a choice of cipher suite, compression and
+// padding. `decrypt_with` reads all of them back from the envelope header.
+pub fn encrypt_with_options(
+    input: &str,
+    key: &Key,
+    options: &EncryptOptions,
+) -> Result<String, Error> {
+    let result = encrypt_bytes_with_options(input.as_bytes(), key, options)?;
+    Ok(STANDARD.encode(result))
+}
 
-    // Base64 encode the final result
-    let encoded_result = encode(&result);
-    encoded_result
+pub fn encrypt_bytes_with_options(
+    input: &[u8],
+    key: &Key,
+    options: &EncryptOptions,
+) -> Result<Vec<u8>, Error> {
+    let header = Header {
+        suite: options.suite,
+        key_source: KeySource::Direct,
+    };
+    let (plaintext, flags) = compress::pack(input, options)?;
+    envelope::seal_with_flags(&header, flags, key, &plaintext, &[])
 }
 
-pub fn calculate_hmac(data: &[u8], key: &[u8]) -> Vec<u8> {
-    use openssl::hash::MessageDigest;
-    use openssl::pkey::PKey;
-    use openssl::sign::Signer;
+// Same as `encrypt_with` but binds `context` to the envelope: strings such as
+// the issuer, the purpose or the recipient, which are authenticated with the
+// payload but not stored in it. `decrypt_with_context` fails with
+// `Error::AuthenticationFailed` unless it is given the same strings in the
+// same order. An empty context is the same as none.
+pub fn encrypt_with_context(input: &str, key: &Key, context: &[&str]) -> Result<String, Error> {
+    let header = Header {
+        suite: CipherSuite::default(),
+        key_source: KeySource::Direct,
+    };
+    let context = envelope::encode_context(context);
+    let result = envelope::seal_with_flags(&header, 0, key, input.as_bytes(), &context)?;
 
-    let pkey = PKey::hmac(key).unwrap();
-    let mut signer = Signer::new(MessageDigest::sha256(), &pkey).unwrap();
-    signer.update(data).unwrap();
-    signer.sign_to_vec().unwrap()
+    Ok(STANDARD.encode(result))
+}
+
+// Encrypts `input` and renders it with `create_img`, binding the watermark
+// name and `context` to the envelope. The image only decrypts with
+// `decrypt_image` given the same watermark and context, so it cannot be
+// re-rendered under another watermark.
+pub fn encrypt_to_image(
+    input: &str,
+    key: &Key,
+    watermark: &str,
+    context: &[&str],
+) -> Result<String, Error> {
+    let encrypted = encrypt_with_context(input, key, &image_context(watermark, context))?;
+    create_img(&encrypted, watermark).ok_or(Error::InvalidImage)
+}
+
+// Same as `encrypt_with` but with an explicit cipher suite. The suite is
+// recorded in the envelope header, so `decrypt_with` picks it up by itself.
+pub fn encrypt_with_suite(input: &str, key: &Key, suite: CipherSuite) -> Result<String, Error> {
+    let options = EncryptOptions {
+        suite,
+        ..EncryptOptions::default()
+    };
+    encrypt_with_options(input, key, &options)
+}
+
+// Encrypts with a key derived from `passphrase`. The random salt and the KDF
+// parameters are stored in the envelope header, so `decrypt_with_passphrase`
+// only needs the passphrase.
+pub fn encrypt_with_passphrase(input: &str, passphrase: &str, kdf: &Kdf) -> Result<String, Error> {
+    kdf.check_bounds()?;
+    let suite = CipherSuite::default();
+    let salt = kdf::generate_salt()?;
+    let key = kdf.derive(passphrase, &salt, suite.key_len())?;
+
+    let header = Header {
+        suite,
+        key_source: KeySource::Passphrase(*kdf, salt),
+    };
+    let result = envelope::seal(&header, &key, input.as_bytes())?;
+
+    Ok(STANDARD.encode(result))
+}
+
+// Encrypts to the holder of the private key matching `recipient`, using
+// AES-256-GCM with a content key only they can recover
+#[cfg(feature = "openssl")]
+pub fn encrypt_to(input: &str, recipient: &PublicKey) -> Result<String, Error> {
+    let suite = CipherSuite::Aes256Gcm;
+    let (key, key_source) = recipient.encapsulate(suite.key_len())?;
+
+    let header = Header { suite, key_source };
+    let result = envelope::seal(&header, &key, input.as_bytes())?;
+
+    Ok(STANDARD.encode(result))
+}
+
+// Encrypts with a random content key that is wrapped separately for each
+// recipient, so every one of them can decrypt with their own passphrase or
+// private key. Up to 255 recipients, at most 8 of them with a passphrase.
+pub fn encrypt_for_recipients(input: &str, recipients: &[Recipient]) -> Result<String, Error> {
+    let suite = CipherSuite::Aes256Gcm;
+    let key = Key::generate(suite.key_len())?;
+
+    let header = Header {
+        suite,
+        key_source: recipients::wrap(&key, recipients)?,
+    };
+    let result = envelope::seal(&header, &key, input.as_bytes())?;
+
+    Ok(STANDARD.encode(result))
+}
+
+// Encrypts with the provider's current key and records its ID in the envelope,
+// so `decrypt_with_provider` finds the right key after it has been rotated
+pub fn encrypt_with_provider(input: &str, provider: &dyn KeyProvider) -> Result<String, Error> {
+    encrypt_with_provider_and_options(input, provider, &EncryptOptions::default())
+}
+
+// `encrypt_with_provider` with the choices of `encrypt_with_options`.
+// `reencrypt` keeps the compression and padding.
+pub fn encrypt_with_provider_and_options(
+    input: &str,
+    provider: &dyn KeyProvider,
+    options: &EncryptOptions,
+) -> Result<String, Error> {
+    let key_id = provider.current_key_id()?;
+    keyring::check_key_id(&key_id)?;
+    let key = provider.key(&key_id)?;
+
+    let header = Header {
+        suite: options.suite,
+        key_source: KeySource::Keyring {
+            key_id: key_id.into_bytes(),
+        },
+    };
+    let (plaintext, flags) = compress::pack(input.as_bytes(), options)?;
+    let result = envelope::seal_with_flags(&header, flags, &key, &plaintext, &[])?;
+
+    Ok(STANDARD.encode(result))
+}
+
+pub fn calculate_hmac(data: &[u8], key: &[u8]) -> Vec<u8> {
+    backend::hmac_sha256(key, data)
 }
 
 pub fn load_watermark(watermark: &str) -> Option<DynamicImage> {
@@ -205,7 +231,7 @@ pub fn load_watermark(watermark: &str) -> Option<DynamicImage> {
       _ => "", // Set a default value for cases not handled
     };
 
-    let decoded = base64::decode(watermark).ok()?;
+    let decoded = STANDARD.decode(watermark).ok()?;
     let cursor = Cursor::new(decoded);
     ImageReader::with_format(cursor, image::ImageFormat::Png)
         .decode()
@@ -213,15 +239,39 @@ pub fn load_watermark(watermark: &str) -> Option<DynamicImage> {
 }
 
 pub fn create_img(ciphertext: &str, watermark: &str) -> Option<String> {
-    let width = ciphertext.len() as u32;
+    render_img(ciphertext, watermark, ColorAlphabet::standard())
+}
+
+// `create_img` with the colors of `alphabet`. Returns `None` if `ciphertext`
+// has a symbol the alphabet does not draw.
+pub fn create_img_with_alphabet(
+    ciphertext: &str,
+    watermark: &str,
+    alphabet: &ColorAlphabet,
+) -> Option<String> {
+    if ciphertext
+        .chars()
+        .any(|char| alphabet.color(char).is_none())
+    {
+        return None;
+    }
+    render_img(ciphertext, watermark, alphabet)
+}
+
+// Symbols missing from `alphabet` are drawn black
+fn render_img(ciphertext: &str, watermark: &str, alphabet: &ColorAlphabet) -> Option<String> {
+    // One column per symbol, which is not one per byte for symbols outside
+    // ASCII in a custom alphabet
+    let width = ciphertext.chars().count() as u32;
     let height = width; // Make the image square
 
     let mut img: RgbaImage = image::ImageBuffer::new(width, height);
 
-    // Generate the image based on the provided colors in the get_color function
+    // Generate the image based on the colors of the alphabet
     let last_column = ciphertext.chars().last();
     let shifted_ciphertext = if let Some(last) = last_column {
-        last.to_string() + &ciphertext[..width as usize - 1]
+        let (rest, _) = ciphertext.split_at(ciphertext.len() - last.len_utf8());
+        last.to_string() + rest
     } else {
         ciphertext.to_string()
     };
@@ -229,7 +279,7 @@ pub fn create_img(ciphertext: &str, watermark: &str) -> Option<String> {
     for x in 0..width {
         let char = shifted_ciphertext.chars().nth(x as usize).unwrap_or('a');
 
-        let color = get_color(char).unwrap_or((0, 0, 0));
+        let color = alphabet.color(char).unwrap_or((0, 0, 0));
 
         for y in 0..height {
             let red = if y == 0 {
@@ -249,7 +299,7 @@ pub fn create_img(ciphertext: &str, watermark: &str) -> Option<String> {
             };
 
             let rgba_color = Rgba([red, green, blue, 255]);
-            img.put_pixel(x as u32, y, rgba_color);
+            img.put_pixel(x, y, rgba_color);
         }
     }
     let watermark_img = load_watermark(watermark);
@@ -262,23 +312,28 @@ pub fn create_img(ciphertext: &str, watermark: &str) -> Option<String> {
     let encoder = PngEncoder::new(&mut buf);
     let dyn_img: DynamicImage = DynamicImage::ImageRgba8(img);
     encoder
-        .encode(&dyn_img.to_rgba8(), width, height, ColorType::Rgba8)
+        .write_image(&dyn_img.to_rgba8(), width, height, ColorType::Rgba8)
         .ok()?;
 
-    let encoded_image = base64::encode(&buf);
+    let encoded_image = STANDARD.encode(&buf);
     Some(encoded_image)
 }
 
 pub fn decode_image_and_extract_text(encoded_image: &str) -> Option<String> {
+    decode_image_with_alphabet(encoded_image, ColorAlphabet::standard())
+}
+
+// Reverse of `create_img_with_alphabet`, for an image drawn with `alphabet`
+pub fn decode_image_with_alphabet(encoded_image: &str, alphabet: &ColorAlphabet) -> Option<String> {
     // Decode the base64 encoded image
-    let image_data = base64::decode(encoded_image).ok()?;
+    let image_data = STANDARD.decode(encoded_image).ok()?;
 
     // Create an image from the decoded data
     let img = ImageReader::new(std::io::Cursor::new(image_data))
         .with_guessed_format()
-        .unwrap()
+        .ok()?
         .decode()
-        .unwrap()
+        .ok()?
         .to_rgba8();
 
     // Get the dimensions of the image
@@ -293,42 +348,350 @@ pub fn decode_image_and_extract_text(encoded_image: &str) -> Option<String> {
         let [r, g, b, _] = pixel.0;
 
         // Convert the pixel into a character using the color mapping
-        if let Some(c) = numbers_to_letter(r, g, b) {
+        if let Some(c) = alphabet.symbol((r, g, b)) {
             extracted_text.push(c);
         }
     }
 
+    if extracted_text.is_empty() {
+        return None;
+    }
     let first_char = extracted_text.remove(0);
     extracted_text.push(first_char);
 
     Some(extracted_text)
 }
 
+// Legacy shim: decrypts with the key that is hardcoded in this crate. Use
+// `decrypt_with` and your own `Key` instead.
+#[deprecated(note = "uses a hardcoded key, use `decrypt_with` with your own `Key`")]
 pub fn decrypts(encoded_result: &str) -> Option<String> {
-    let key = "welovenfts"; // Define the key here
-    let mut padded_key = key.as_bytes().to_vec();
-    while padded_key.len() < 16 {
-        padded_key.push(b'\0');
-    }
+    let result_bytes = STANDARD.decode(encoded_result).ok()?;
+    let decrypted = if envelope::is_versioned(&result_bytes) {
+        decrypt_bytes(&result_bytes, &Key::legacy()).ok()?
+    } else {
+        // Envelopes from before the versioned format
+        SecretBytes::new(legacy::open(&Key::legacy(), &result_bytes).ok()?)
+    };
+    decrypted
+        .into_string()
+        .ok()
+        .map(|decrypted| decrypted.as_str().to_string())
+}
 
+// The decrypt functions return the plaintext in a `SecretString` or
+// `SecretBytes`, which is zeroized when dropped. Refuses plaintext that is
+// not UTF-8 rather than replacing characters, use `decrypt_bytes` for binary
+// data.
+pub fn decrypt_with(encoded_result: &str, key: &Key) -> Result<SecretString, Error> {
     // Decode the base64 encoded result
-    let result_bytes = base64::decode(encoded_result).ok()?;
-
-    // Extract the IV, HMAC, and ciphertext from the result
-    let iv = &result_bytes[..16];
-    let hmac = &result_bytes[16..48];
-    let ciphertext = &result_bytes[48..];
-
-    // Verify the HMAC
-    let hmac_calculated = calculate_hmac(ciphertext, &padded_key);
-    if hmac_calculated != hmac {
-        println!("HMAC verification failed");
-        return None; // HMAC verification failed, the data may have been tampered with
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    decrypt_bytes(&result_bytes, key)?.into_string()
+}
+
+// Decrypts a raw envelope from `encrypt_bytes`. Anything not in the
+// versioned format is `Error::Malformed`; the output of the original
+// `encrypts` is opened with `decrypt_legacy`.
+pub fn decrypt_bytes(data: &[u8], key: &Key) -> Result<SecretBytes, Error> {
+    let envelope = envelope::parse(data)?;
+    if !matches!(envelope.header.key_source, KeySource::Direct) {
+        return Err(Error::KeyKindMismatch);
+    }
+    open_envelope(&envelope, key)
+}
+
+// Decrypts an envelope from `encrypt_with_context`, which must be given the
+// same context
+pub fn decrypt_with_context(
+    encoded_result: &str,
+    key: &Key,
+    context: &[&str],
+) -> Result<SecretString, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    if !matches!(envelope.header.key_source, KeySource::Direct) {
+        return Err(Error::KeyKindMismatch);
     }
+    let context = envelope::encode_context(context);
+    let decrypted_data = open_envelope_with_context(&envelope, key, &context)?;
+
+    decrypted_data.into_string()
+}
+
+// Reverse of `encrypt_to_image`
+pub fn decrypt_image(
+    encoded_image: &str,
+    key: &Key,
+    watermark: &str,
+    context: &[&str],
+) -> Result<SecretString, Error> {
+    let extracted_text = decode_image_and_extract_text(encoded_image).ok_or(Error::InvalidImage)?;
+
+    decrypt_with_context(&extracted_text, key, &image_context(watermark, context))
+}
+
+// `decrypt_with` for envelopes that must not be older than `max_age`. The
+// issued at time is part of the authenticated header, so it cannot be changed
+// without the key. `skew` is how far the clocks of the encrypting and
+// decrypting machines may disagree, in either direction. Envelopes without a
+// timestamp, including those from before the versioned format, fail with
+// `Error::Expired`.
+pub fn decrypt_with_ttl(
+    encoded_result: &str,
+    key: &Key,
+    max_age: Duration,
+    skew: Duration,
+) -> Result<SecretString, Error> {
+    decrypt_with_ttl_at(encoded_result, key, max_age, skew, SystemTime::now())
+}
+
+// `decrypt_with_ttl` as if the current time were `now`
+pub fn decrypt_with_ttl_at(
+    encoded_result: &str,
+    key: &Key,
+    max_age: Duration,
+    skew: Duration,
+    now: SystemTime,
+) -> Result<SecretString, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    if !envelope::is_versioned(&result_bytes) {
+        return Err(Error::Expired);
+    }
+    let envelope = envelope::parse(&result_bytes)?;
+    if !matches!(envelope.header.key_source, KeySource::Direct) {
+        return Err(Error::KeyKindMismatch);
+    }
+
+    // Only trust the timestamp once the header has authenticated
+    let decrypted_data = open_envelope(&envelope, key)?;
+    check_age(envelope.issued_at, max_age, skew, now)?;
+
+    decrypted_data.into_string()
+}
+
+pub fn decrypt_with_passphrase(
+    encoded_result: &str,
+    passphrase: &str,
+) -> Result<SecretString, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+
+    // Re-derive the key from the salt and parameters stored in the envelope
+    let key_len = envelope.header.suite.key_len();
+    let key = match &envelope.header.key_source {
+        KeySource::Passphrase(kdf, salt) => kdf.derive(passphrase, salt, key_len)?,
+        KeySource::Recipients(stanzas) => {
+            recipients::unwrap_with_passphrase(stanzas, passphrase, key_len)?
+        }
+        _ => return Err(Error::KeyKindMismatch),
+    };
+    let decrypted_data = open_envelope(&envelope, &key)?;
+
+    decrypted_data.into_string()
+}
+
+// Lists who can open the envelope, without decrypting it
+pub fn list_recipients(encoded_result: &str) -> Result<Vec<RecipientInfo>, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    Ok(recipients::list(&envelope.header.key_source))
+}
+
+// Decrypts an envelope produced by `encrypt_to`, or one from
+// `encrypt_for_recipients` that lists the key
+#[cfg(feature = "openssl")]
+pub fn decrypt_with_private_key(
+    encoded_result: &str,
+    private_key: &PrivateKey,
+) -> Result<SecretString, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+
+    let key_len = envelope.header.suite.key_len();
+    let key = match &envelope.header.key_source {
+        KeySource::Recipients(stanzas) => {
+            recipients::unwrap_with_private_key(stanzas, private_key, key_len)?
+        }
+        key_source => private_key.decapsulate(key_source, key_len)?,
+    };
+    let decrypted_data = open_envelope(&envelope, &key)?;
+
+    decrypted_data.into_string()
+}
+
+// Decrypts an envelope from `encrypt_with_provider` with whichever key it
+// names, current or retired
+pub fn decrypt_with_provider(
+    encoded_result: &str,
+    provider: &dyn KeyProvider,
+) -> Result<SecretString, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    let key = provider.key(&keyring_key_id(&envelope)?)?;
+    let decrypted_data = open_envelope(&envelope, &key)?;
+
+    decrypted_data.into_string()
+}
+
+// ID of the key an envelope from `encrypt_with_provider` was encrypted with
+pub fn envelope_key_id(encoded_result: &str) -> Result<String, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    keyring_key_id(&envelope::parse(&result_bytes)?)
+}
+
+// When the envelope was sealed, `None` if it has no timestamp. This is read
+// without the key and so not authenticated, use `decrypt_with_ttl` to enforce
+// an age.
+pub fn envelope_issued_at(encoded_result: &str) -> Result<Option<SystemTime>, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    envelope
+        .issued_at
+        .map(|issued_at| {
+            // Not every u64 of seconds fits a SystemTime
+            UNIX_EPOCH
+                .checked_add(Duration::from_secs(issued_at))
+                .ok_or(Error::Malformed)
+        })
+        .transpose()
+}
+
+// Decrypts with the key the envelope names and encrypts again with the
+// provider's current key. Used to migrate envelopes off a retired key. The
+// original issued at time is kept, so rotating keys does not extend a TTL.
+pub fn reencrypt(encoded_result: &str, provider: &dyn KeyProvider) -> Result<String, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    let key = provider.key(&keyring_key_id(&envelope)?)?;
+    // Left compressed and padded as it is, and sealed again with the same
+    // flags, so rotating keys does not undo either
+    let packed = SecretBytes::new(envelope.header.suite.open(
+        &key,
+        envelope.payload,
+        envelope.header_bytes,
+    )?);
+
+    let key_id = provider.current_key_id()?;
+    keyring::check_key_id(&key_id)?;
+    let header = Header {
+        suite: envelope.header.suite,
+        key_source: KeySource::Keyring {
+            key_id: key_id.as_bytes().to_vec(),
+        },
+    };
+    let result = envelope::seal_at(
+        &header,
+        envelope.flags & !envelope::FLAG_TIMESTAMP,
+        envelope.issued_at,
+        &provider.key(&key_id)?,
+        &packed,
+        &[],
+    )?;
+
+    Ok(STANDARD.encode(result))
+}
+
+// `reencrypt` for the envelope in the first row of `encoded_image`, returning
+// a new image with the given watermark
+pub fn reencrypt_image(
+    encoded_image: &str,
+    watermark: &str,
+    provider: &dyn KeyProvider,
+) -> Result<String, Error> {
+    let extracted_text = decode_image_and_extract_text(encoded_image).ok_or(Error::InvalidImage)?;
+
+    let encoded_result = reencrypt(&extracted_text, provider)?;
+    create_img(&encoded_result, watermark).ok_or(Error::InvalidImage)
+}
+
+fn keyring_key_id(envelope: &Envelope) -> Result<String, Error> {
+    match &envelope.header.key_source {
+        KeySource::Keyring { key_id } => {
+            String::from_utf8(key_id.clone()).map_err(|_| Error::Malformed)
+        }
+        _ => Err(Error::KeyKindMismatch),
+    }
+}
+
+// The watermark name is bound as the first context string
+fn image_context<'a>(watermark: &'a str, context: &[&'a str]) -> Vec<&'a str> {
+    let mut image_context = vec![watermark];
+    image_context.extend_from_slice(context);
+    image_context
+}
+
+fn check_age(
+    issued_at: Option<u64>,
+    max_age: Duration,
+    skew: Duration,
+    now: SystemTime,
+) -> Result<(), Error> {
+    let issued_at = issued_at.ok_or(Error::Expired)?;
+    let now = envelope::unix_time(now);
+    let skew = skew.as_secs();
+
+    let from_the_future = issued_at > now.saturating_add(skew);
+    let too_old = now
+        > issued_at
+            .saturating_add(max_age.as_secs())
+            .saturating_add(skew);
+    if from_the_future || too_old {
+        return Err(Error::Expired);
+    }
+    Ok(())
+}
+
+fn open_envelope(envelope: &Envelope, key: &Key) -> Result<SecretBytes, Error> {
+    open_envelope_with_context(envelope, key, &[])
+}
+
+fn open_envelope_with_context(
+    envelope: &Envelope,
+    key: &Key,
+    context: &[u8],
+) -> Result<SecretBytes, Error> {
+    let aad = [envelope.header_bytes, context].concat();
+    let decrypted_data = envelope.header.suite.open(key, envelope.payload, &aad)?;
+    compress::unpack(SecretBytes::new(decrypted_data), envelope.flags)
+}
+
+// Signs an envelope produced by any of the encrypt functions. The signature and
+// the signer's key ID travel with the envelope; decryption ignores them.
+#[cfg(feature = "openssl")]
+pub fn sign_envelope(encoded_result: &str, signer: &SigningKey) -> Result<String, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    if !envelope::is_versioned(&result_bytes) {
+        return Err(Error::Malformed);
+    }
+    let signed = envelope::sign(&result_bytes, signer)?;
+    Ok(STANDARD.encode(signed))
+}
+
+// Key ID of whoever signed the envelope, to pick the right `VerifyingKey`
+pub fn signer_key_id(encoded_result: &str) -> Result<Vec<u8>, Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    let signature = envelope.signature.ok_or(Error::MissingSignature)?;
+    Ok(signature.key_id.to_vec())
+}
+
+#[cfg(feature = "openssl")]
+pub fn verify_envelope(encoded_result: &str, verifier: &VerifyingKey) -> Result<(), Error> {
+    let result_bytes = STANDARD.decode(encoded_result)?;
+    let envelope = envelope::parse(&result_bytes)?;
+    let signature = envelope.signature.ok_or(Error::MissingSignature)?;
+    if signature.key_id != verifier.key_id()? {
+        return Err(Error::InvalidSignature);
+    }
+    verifier.verify(signature.algorithm, signature.signed, signature.value)
+}
 
-    // Decrypt the ciphertext
-    let cipher = Cipher::aes_128_cbc();
-    let decrypted_data = decrypt(cipher, &padded_key, Some(iv), ciphertext).ok()?;
+// Checks that the envelope in the first row of `encoded_image` was signed by
+// `verifier`, and returns the envelope so it can be decrypted
+#[cfg(feature = "openssl")]
+pub fn verify_image(encoded_image: &str, verifier: &VerifyingKey) -> Result<String, Error> {
+    let extracted_text = decode_image_and_extract_text(encoded_image).ok_or(Error::InvalidImage)?;
 
-    Some(String::from_utf8_lossy(&decrypted_data).to_string())
+    verify_envelope(&extracted_text, verifier)?;
+    Ok(extracted_text)
 }
@@ -0,0 +1,237 @@
+use crate::color::{delta_e76, Lab};
+use crate::{ColorAlphabet, Error};
+use std::collections::HashMap;
+
+// Candidate colors are an sRGB grid with this many levels per channel
+const LEVELS: u32 = 33;
+
+// Limits on the colors `generate_palette` may pick, in CIELAB
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaletteConstraints {
+    // Lightness range, 0 (black) to 100 (white)
+    pub min_lightness: f64,
+    pub max_lightness: f64,
+    // Largest chroma, the distance from grey. Low values keep a pastel look.
+    pub max_chroma: Option<f64>,
+    // Stay inside a rough approximation of what offset printing on coated
+    // paper reproduces: no colors darker than ink or brighter than paper, and
+    // none of the saturated screen colors that CMYK inks cannot mix
+    pub print_gamut: bool,
+}
+
+impl Default for PaletteConstraints {
+    fn default() -> PaletteConstraints {
+        PaletteConstraints {
+            min_lightness: 0.0,
+            max_lightness: 100.0,
+            max_chroma: None,
+            print_gamut: false,
+        }
+    }
+}
+
+impl PaletteConstraints {
+    // Light, soft colors like those of the standard alphabet
+    pub fn pastel() -> PaletteConstraints {
+        PaletteConstraints {
+            min_lightness: 70.0,
+            max_lightness: 97.0,
+            max_chroma: Some(40.0),
+            print_gamut: false,
+        }
+    }
+
+    fn allows(&self, lab: Lab) -> bool {
+        let chroma = lab.a.hypot(lab.b);
+        let print = !self.print_gamut || (lab.l >= 15.0 && lab.l <= 95.0 && chroma <= 60.0);
+        lab.l >= self.min_lightness
+            && lab.l <= self.max_lightness
+            && chroma <= self.max_chroma.unwrap_or(f64::INFINITY)
+            && print
+    }
+}
+
+// A palette from `generate_palette` and how well separated it is
+#[derive(Clone, Debug)]
+pub struct GeneratedPalette {
+    pub alphabet: ColorAlphabet,
+    // The two symbols whose colors are closest, and their CIE76 distance: the
+    // worst case for telling symbols apart
+    pub closest_pair: (char, char),
+    pub min_distance: f64,
+}
+
+impl GeneratedPalette {
+    // The palette in the file format of `ColorAlphabet::from_toml`, with its
+    // own minimum distance so that it loads
+    pub fn to_toml(&self) -> String {
+        let mut colors = toml::Table::new();
+        for &(symbol, (r, g, b)) in self.alphabet.entries() {
+            let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+            colors.insert(symbol.to_string(), toml::Value::String(hex));
+        }
+        let mut file = toml::Table::new();
+        // Rounded down, so the palette passes its own check
+        let min_distance = (self.min_distance * 100.0).floor() / 100.0;
+        file.insert("min_distance".into(), toml::Value::Float(min_distance));
+        file.insert("colors".into(), toml::Value::Table(colors));
+        file.to_string()
+    }
+}
+
+// Pick a color for every symbol of `symbols`, as far apart from each other in
+// CIELAB as the search finds within `constraints`. Fails with
+// `Error::InvalidAlphabet` for repeated symbols, fewer than two, or
+// constraints that leave fewer colors than symbols.
+//
+// The search is deterministic: a greedy farthest-point pick over a grid of
+// sRGB colors, then moves of single colors to nearby grid colors for as long
+// as that pushes them further from their nearest neighbour.
+pub fn generate_palette(
+    symbols: &str,
+    constraints: &PaletteConstraints,
+) -> Result<GeneratedPalette, Error> {
+    let symbols: Vec<char> = symbols.chars().collect();
+    let candidates = candidates(constraints);
+    if symbols.len() < 2 || candidates.len() < symbols.len() {
+        return Err(Error::InvalidAlphabet);
+    }
+
+    let mut chosen = farthest_points(&candidates, symbols.len());
+    refine(&candidates, &mut chosen);
+
+    let entries: Vec<(char, (u8, u8, u8))> = symbols
+        .iter()
+        .zip(&chosen)
+        .map(|(&symbol, &i)| (symbol, candidates[i].0))
+        .collect();
+    let alphabet = ColorAlphabet::new(&entries)?;
+    let (first, second, min_distance) = alphabet.closest_pair().ok_or(Error::InvalidAlphabet)?;
+    Ok(GeneratedPalette {
+        alphabet,
+        closest_pair: (first, second),
+        min_distance,
+    })
+}
+
+fn level(i: u32) -> u8 {
+    (i * 255 / (LEVELS - 1)) as u8
+}
+
+fn candidates(constraints: &PaletteConstraints) -> Vec<((u8, u8, u8), Lab)> {
+    let mut out = Vec::new();
+    for r in 0..LEVELS {
+        for g in 0..LEVELS {
+            for b in 0..LEVELS {
+                let rgb = (level(r), level(g), level(b));
+                let lab = Lab::from_rgb(rgb);
+                if constraints.allows(lab) {
+                    out.push((rgb, lab));
+                }
+            }
+        }
+    }
+    out
+}
+
+// Start from the candidate furthest from the middle of all of them, then keep
+// adding the candidate furthest from those already picked
+fn farthest_points(candidates: &[((u8, u8, u8), Lab)], count: usize) -> Vec<usize> {
+    let n = candidates.len() as f64;
+    let center = Lab {
+        l: candidates.iter().map(|(_, lab)| lab.l).sum::<f64>() / n,
+        a: candidates.iter().map(|(_, lab)| lab.a).sum::<f64>() / n,
+        b: candidates.iter().map(|(_, lab)| lab.b).sum::<f64>() / n,
+    };
+    let to_center: Vec<f64> = candidates
+        .iter()
+        .map(|(_, lab)| delta_e76(*lab, center))
+        .collect();
+    let first = argmax(&to_center);
+
+    // Distance from every candidate to the nearest picked color
+    let mut nearest: Vec<f64> = candidates
+        .iter()
+        .map(|(_, lab)| delta_e76(*lab, candidates[first].1))
+        .collect();
+    let mut chosen = vec![first];
+    while chosen.len() < count {
+        let next = argmax(&nearest);
+        chosen.push(next);
+        for (distance, (_, lab)) in nearest.iter_mut().zip(candidates) {
+            *distance = distance.min(delta_e76(*lab, candidates[next].1));
+        }
+    }
+    chosen
+}
+
+// Move each color to a neighbouring grid color while that increases its
+// distance to its nearest neighbour. No pair that does not involve the moved
+// color changes, so the minimum distance of the palette never shrinks.
+fn refine(candidates: &[((u8, u8, u8), Lab)], chosen: &mut [usize]) {
+    let index: HashMap<(u8, u8, u8), usize> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, (rgb, _))| (*rgb, i))
+        .collect();
+    let grid = |channel: u8| (channel as u32 * (LEVELS - 1) + 127) / 255;
+
+    let nearest = |chosen: &[usize], skip: usize, lab: Lab| {
+        chosen
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != skip)
+            .map(|(_, &other)| delta_e76(lab, candidates[other].1))
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    // Bounded, though it stops well before in practice
+    for _ in 0..100 {
+        let mut moved = false;
+        for i in 0..chosen.len() {
+            let ((r, g, b), lab) = candidates[chosen[i]];
+            let mut best = (chosen[i], nearest(chosen, i, lab));
+            for dr in -2i32..=2 {
+                for dg in -2i32..=2 {
+                    for db in -2i32..=2 {
+                        let step = |channel: u8, delta: i32| {
+                            let at = grid(channel) as i32 + delta;
+                            (0..LEVELS as i32).contains(&at).then(|| level(at as u32))
+                        };
+                        let (Some(r), Some(g), Some(b)) = (step(r, dr), step(g, dg), step(b, db))
+                        else {
+                            continue;
+                        };
+                        let Some(&candidate) = index.get(&(r, g, b)) else {
+                            continue;
+                        };
+                        if chosen.contains(&candidate) {
+                            continue;
+                        }
+                        let distance = nearest(chosen, i, candidates[candidate].1);
+                        if distance > best.1 {
+                            best = (candidate, distance);
+                        }
+                    }
+                }
+            }
+            if best.0 != chosen[i] {
+                chosen[i] = best.0;
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+}
+
+fn argmax(values: &[f64]) -> usize {
+    let mut best = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value > values[best] {
+            best = i;
+        }
+    }
+    best
+}
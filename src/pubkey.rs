@@ -0,0 +1,204 @@
+use crate::envelope::KeySource;
+use crate::hkdf::hkdf_sha256;
+use crate::{Error, Key};
+use openssl::derive::Deriver;
+use openssl::encrypt::{Decrypter, Encrypter};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::rsa::{Padding, Rsa};
+use zeroize::Zeroizing;
+
+const MIN_RSA_BITS: u32 = 2048;
+const KEY_ID_LEN: usize = 8;
+
+// A recipient's public key. Envelopes encrypted to it can only be opened with
+// the matching `PrivateKey`.
+//
+// X25519 keys use ECDH with an ephemeral key and HKDF-SHA256 to derive the
+// content key. RSA keys (at least 2048 bits) wrap a random content key with
+// RSA-OAEP-SHA256, for partners that cannot use X25519 yet.
+#[derive(Clone)]
+pub struct PublicKey {
+    pkey: PKey<Public>,
+}
+
+#[derive(Clone)]
+pub struct PrivateKey {
+    pkey: PKey<Private>,
+}
+
+impl PublicKey {
+    pub fn from_pem(pem: &[u8]) -> Result<PublicKey, Error> {
+        PublicKey::new(PKey::public_key_from_pem(pem)?)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<PublicKey, Error> {
+        PublicKey::new(PKey::public_key_from_der(der)?)
+    }
+
+    // SubjectPublicKeyInfo PEM
+    pub fn to_pem(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.public_key_to_pem()?)
+    }
+
+    // SubjectPublicKeyInfo DER
+    pub fn to_der(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.public_key_to_der()?)
+    }
+
+    // The first 8 bytes of the SHA-256 of the SubjectPublicKeyInfo DER
+    pub fn key_id(&self) -> Result<Vec<u8>, Error> {
+        key_id(&self.pkey.public_key_to_der()?)
+    }
+
+    fn new(pkey: PKey<Public>) -> Result<PublicKey, Error> {
+        check_key_type(pkey.id(), pkey.bits())?;
+        Ok(PublicKey { pkey })
+    }
+
+    // Produce a fresh content key for this recipient, along with what the
+    // recipient needs to recover it
+    pub(crate) fn encapsulate(&self, key_len: usize) -> Result<(Key, KeySource), Error> {
+        match self.pkey.id() {
+            Id::X25519 => {
+                let ephemeral = PKey::generate_x25519()?;
+                let ephemeral_public = ephemeral.raw_public_key()?;
+                let key = x25519_content_key(
+                    &ephemeral,
+                    &self.pkey,
+                    &ephemeral_public,
+                    &self.pkey.raw_public_key()?,
+                    key_len,
+                )?;
+                Ok((
+                    key,
+                    KeySource::X25519 {
+                        ephemeral: ephemeral_public,
+                    },
+                ))
+            }
+            _ => {
+                let key = Key::generate(key_len)?;
+                let mut encrypter = Encrypter::new(&self.pkey)?;
+                encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+                encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+                let mut wrapped = vec![0; encrypter.encrypt_len(key.as_bytes())?];
+                let len = encrypter.encrypt(key.as_bytes(), &mut wrapped)?;
+                wrapped.truncate(len);
+                Ok((key, KeySource::RsaOaep { wrapped }))
+            }
+        }
+    }
+}
+
+impl PrivateKey {
+    pub fn generate_x25519() -> Result<PrivateKey, Error> {
+        Ok(PrivateKey {
+            pkey: PKey::generate_x25519()?,
+        })
+    }
+
+    pub fn generate_rsa(bits: u32) -> Result<PrivateKey, Error> {
+        if bits < MIN_RSA_BITS {
+            return Err(Error::UnsupportedKeyType);
+        }
+        Ok(PrivateKey {
+            pkey: PKey::from_rsa(Rsa::generate(bits)?)?,
+        })
+    }
+
+    pub fn from_pem(pem: &[u8]) -> Result<PrivateKey, Error> {
+        PrivateKey::new(PKey::private_key_from_pem(pem)?)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<PrivateKey, Error> {
+        PrivateKey::new(PKey::private_key_from_der(der)?)
+    }
+
+    // Unencrypted PKCS#8 PEM
+    pub fn to_pem(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.private_key_to_pem_pkcs8()?)
+    }
+
+    // Unencrypted PKCS#8 DER
+    pub fn to_der(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.private_key_to_pkcs8()?)
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        PublicKey::from_der(&self.pkey.public_key_to_der()?)
+    }
+
+    fn new(pkey: PKey<Private>) -> Result<PrivateKey, Error> {
+        check_key_type(pkey.id(), pkey.bits())?;
+        Ok(PrivateKey { pkey })
+    }
+
+    // Recover the content key `PublicKey::encapsulate` produced
+    pub(crate) fn decapsulate(&self, source: &KeySource, key_len: usize) -> Result<Key, Error> {
+        match (self.pkey.id(), source) {
+            (Id::X25519, KeySource::X25519 { ephemeral }) => {
+                let ephemeral_key = PKey::public_key_from_raw_bytes(ephemeral, Id::X25519)
+                    .map_err(|_| Error::Malformed)?;
+                x25519_content_key(
+                    &self.pkey,
+                    &ephemeral_key,
+                    ephemeral,
+                    &self.pkey.raw_public_key()?,
+                    key_len,
+                )
+            }
+            (Id::RSA, KeySource::RsaOaep { wrapped }) => {
+                let mut decrypter = Decrypter::new(&self.pkey)?;
+                decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                decrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+                decrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+                let mut key = vec![0; decrypter.decrypt_len(wrapped)?];
+                let len = decrypter
+                    .decrypt(wrapped, &mut key)
+                    .map_err(|_| Error::AuthenticationFailed)?;
+                key.truncate(len);
+
+                let key = Key::from_vec(key);
+                key.check_len(key_len)?;
+                Ok(key)
+            }
+            _ => Err(Error::KeyKindMismatch),
+        }
+    }
+}
+
+pub(crate) fn key_id(public_der: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(hash(MessageDigest::sha256(), public_der)?[..KEY_ID_LEN].to_vec())
+}
+
+fn check_key_type(id: Id, bits: u32) -> Result<(), Error> {
+    match id {
+        Id::X25519 => Ok(()),
+        Id::RSA if bits >= MIN_RSA_BITS => Ok(()),
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+// ECDH between `private` and `peer`, then HKDF-SHA256 salted with both
+// public keys so the content key is bound to this exact pair
+fn x25519_content_key<T>(
+    private: &PKey<Private>,
+    peer: &PKey<T>,
+    ephemeral_public: &[u8],
+    recipient_public: &[u8],
+    key_len: usize,
+) -> Result<Key, Error>
+where
+    T: openssl::pkey::HasPublic,
+{
+    let mut deriver = Deriver::new(private)?;
+    deriver.set_peer(peer)?;
+    let shared = Zeroizing::new(deriver.derive_to_vec()?);
+
+    let salt = [ephemeral_public, recipient_public].concat();
+    hkdf_sha256(&shared, &salt, b"colorsquares x25519", key_len)
+}
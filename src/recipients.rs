@@ -0,0 +1,157 @@
+use crate::envelope::{KeySource, Stanza};
+use crate::kdf::{self, Kdf};
+use crate::{CipherSuite, Error, Key};
+#[cfg(feature = "openssl")]
+use crate::{PrivateKey, PublicKey};
+
+// Content keys are wrapped for each recipient with a key-encryption key of
+// this suite, with the recipient's key ID as associated data
+const WRAP_SUITE: CipherSuite = CipherSuite::Aes256Gcm;
+
+const MAX_RECIPIENTS: usize = u8::MAX as usize;
+
+// Every passphrase recipient may cost a full KDF run to try, so an envelope
+// may have only a few of them
+const MAX_PASSPHRASE_RECIPIENTS: usize = 8;
+
+// Someone who can open a multi-recipient envelope with their own credential
+pub enum Recipient<'a> {
+    Passphrase(&'a str, Kdf),
+    #[cfg(feature = "openssl")]
+    PublicKey(&'a PublicKey),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecipientKind {
+    Passphrase,
+    X25519,
+    Rsa,
+}
+
+// What `list_recipients` reports about each recipient. Passphrase recipients
+// have an empty key ID; public key recipients have `PublicKey::key_id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecipientInfo {
+    pub kind: RecipientKind,
+    pub key_id: Vec<u8>,
+}
+
+// Wrap `content_key` once for every recipient
+pub(crate) fn wrap(content_key: &Key, recipients: &[Recipient]) -> Result<KeySource, Error> {
+    let passphrases = recipients
+        .iter()
+        .filter(|recipient| matches!(recipient, Recipient::Passphrase(..)))
+        .count();
+    if recipients.is_empty()
+        || recipients.len() > MAX_RECIPIENTS
+        || passphrases > MAX_PASSPHRASE_RECIPIENTS
+    {
+        return Err(Error::InvalidRecipients);
+    }
+
+    let mut stanzas = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let (kek, source, key_id) = match recipient {
+            Recipient::Passphrase(passphrase, kdf) => {
+                kdf.check_bounds()?;
+                let salt = kdf::generate_salt()?;
+                let kek = kdf.derive(passphrase, &salt, WRAP_SUITE.key_len())?;
+                (kek, KeySource::Passphrase(*kdf, salt), Vec::new())
+            }
+            #[cfg(feature = "openssl")]
+            Recipient::PublicKey(public_key) => {
+                let (kek, source) = public_key.encapsulate(WRAP_SUITE.key_len())?;
+                (kek, source, public_key.key_id()?)
+            }
+        };
+        let wrapped = WRAP_SUITE.seal(&kek, content_key.as_bytes(), &key_id)?;
+        stanzas.push(Stanza {
+            source,
+            key_id,
+            wrapped,
+        });
+    }
+    Ok(KeySource::Recipients(stanzas))
+}
+
+// Try every passphrase recipient in turn. Envelopes with more of them than
+// `wrap` writes are refused before deriving any key.
+pub(crate) fn unwrap_with_passphrase(
+    stanzas: &[Stanza],
+    passphrase: &str,
+    key_len: usize,
+) -> Result<Key, Error> {
+    let passphrases = stanzas
+        .iter()
+        .filter(|stanza| matches!(stanza.source, KeySource::Passphrase(..)))
+        .count();
+    if passphrases > MAX_PASSPHRASE_RECIPIENTS {
+        return Err(Error::InvalidRecipients);
+    }
+
+    let mut result = Err(Error::KeyKindMismatch);
+    for stanza in stanzas {
+        if let KeySource::Passphrase(kdf, salt) = &stanza.source {
+            let kek = kdf.derive(passphrase, salt, WRAP_SUITE.key_len())?;
+            result = unwrap(stanza, &kek, key_len);
+            if result.is_ok() {
+                break;
+            }
+        }
+    }
+    result
+}
+
+// Only the recipients listed under this key's ID are tried
+#[cfg(feature = "openssl")]
+pub(crate) fn unwrap_with_private_key(
+    stanzas: &[Stanza],
+    private_key: &PrivateKey,
+    key_len: usize,
+) -> Result<Key, Error> {
+    let key_id = private_key.public_key()?.key_id()?;
+
+    let mut result = Err(Error::NotARecipient);
+    for stanza in stanzas.iter().filter(|stanza| stanza.key_id == key_id) {
+        let kek = private_key.decapsulate(&stanza.source, WRAP_SUITE.key_len())?;
+        result = unwrap(stanza, &kek, key_len);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+pub(crate) fn list(key_source: &KeySource) -> Vec<RecipientInfo> {
+    let info = |source: &KeySource, key_id: &[u8]| {
+        let kind = match source {
+            KeySource::Passphrase(..) => RecipientKind::Passphrase,
+            KeySource::X25519 { .. } => RecipientKind::X25519,
+            KeySource::RsaOaep { .. } => RecipientKind::Rsa,
+            KeySource::Direct | KeySource::Keyring { .. } | KeySource::Recipients(_) => {
+                return None
+            }
+        };
+        Some(RecipientInfo {
+            kind,
+            key_id: key_id.to_vec(),
+        })
+    };
+
+    match key_source {
+        KeySource::Recipients(stanzas) => stanzas
+            .iter()
+            .filter_map(|stanza| info(&stanza.source, &stanza.key_id))
+            .collect(),
+        // Single recipient envelopes do not record a key ID
+        source => info(source, &[]).into_iter().collect(),
+    }
+}
+
+fn unwrap(stanza: &Stanza, kek: &Key, key_len: usize) -> Result<Key, Error> {
+    let content_key = Key::from_vec(WRAP_SUITE.open(kek, &stanza.wrapped, &stanza.key_id)?);
+    if content_key.len() != key_len {
+        return Err(Error::Malformed);
+    }
+    Ok(content_key)
+}
@@ -0,0 +1,89 @@
+use crate::Error;
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+// Decrypted data. The bytes are overwritten with zeros when the value is
+// dropped, and `Debug` never prints them.
+pub struct SecretBytes(Vec<u8>);
+
+// Decrypted text, with the same guarantees as `SecretBytes`
+pub struct SecretString(String);
+
+impl SecretBytes {
+    pub fn new(bytes: Vec<u8>) -> SecretBytes {
+        SecretBytes(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // A vector that reallocates while growing leaves the old copy behind
+    // unzeroized, so only grow it where the final size is not known
+    pub(crate) fn as_mut_vec(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+
+    // Fails rather than replacing characters when the bytes are not UTF-8.
+    // The bytes move into the string without being copied.
+    pub(crate) fn into_string(mut self) -> Result<SecretString, Error> {
+        match String::from_utf8(std::mem::take(&mut self.0)) {
+            Ok(string) => Ok(SecretString(string)),
+            Err(err) => {
+                err.into_bytes().zeroize();
+                Err(Error::InvalidUtf8)
+            }
+        }
+    }
+}
+
+impl SecretString {
+    pub fn new(string: String) -> SecretString {
+        SecretString(string)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED])")
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([REDACTED])")
+    }
+}
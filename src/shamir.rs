@@ -0,0 +1,245 @@
+use crate::backend;
+use crate::envelope::{self, Header, KeySource, Reader};
+use crate::{create_img, decode_image_and_extract_text, open_envelope_with_context};
+use crate::{CipherSuite, Error, Key, SecretBytes};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 3] = b"CSH";
+const VERSION: u8 = 1;
+const GROUP_ID_LEN: usize = 16;
+const SUITE: CipherSuite = CipherSuite::Aes256Gcm;
+
+// Share layout, one per image:
+//
+//   magic       3 bytes  "CSH"
+//   version     1 byte
+//   threshold   1 byte   shares needed to recover the payload
+//   group id   16 bytes  random, the same for every share of one split
+//   index       1 byte   x coordinate of this share, 1 to 255
+//   key share  32 bytes  y coordinates for each byte of the content key
+//   envelope             the payload sealed under the content key, the same
+//                        in every share
+//
+// The payload is encrypted with a random AES-256-GCM content key and only the
+// key is split, with Shamir's scheme over GF(2^8): every key byte is the
+// constant term of a random polynomial of degree threshold - 1, and share i
+// holds each polynomial evaluated at i. Any threshold shares determine the
+// polynomials, fewer reveal nothing about the key. The envelope
+// authenticates everything before the index as associated data.
+
+// Encrypt `input` and split it into `shares` shares, any `threshold` of which
+// recover it. Returns each share base64 encoded, in index order.
+pub fn split_secret(input: &[u8], threshold: u8, shares: u8) -> Result<Vec<String>, Error> {
+    if threshold < 2 || threshold > shares {
+        return Err(Error::InvalidThreshold);
+    }
+
+    let mut group_id = [0; GROUP_ID_LEN];
+    backend::random_bytes(&mut group_id)?;
+    let mut prefix = MAGIC.to_vec();
+    prefix.push(VERSION);
+    prefix.push(threshold);
+    prefix.extend_from_slice(&group_id);
+
+    let key = Key::generate(SUITE.key_len())?;
+    let header = Header {
+        suite: SUITE,
+        key_source: KeySource::Direct,
+    };
+    let sealed = envelope::seal_with_flags(&header, 0, &key, input, &prefix)?;
+
+    let key_shares = split(key.as_bytes(), threshold, shares)?;
+    Ok(key_shares
+        .iter()
+        .enumerate()
+        .map(|(i, key_share)| {
+            let mut share = Zeroizing::new(prefix.clone());
+            share.push(i as u8 + 1);
+            share.extend_from_slice(key_share);
+            share.extend_from_slice(&sealed);
+            STANDARD.encode(&share)
+        })
+        .collect())
+}
+
+// `split_secret` with every share rendered by `create_img`
+pub fn split_to_images(
+    input: &[u8],
+    threshold: u8,
+    shares: u8,
+    watermark: &str,
+) -> Result<Vec<String>, Error> {
+    split_secret(input, threshold, shares)?
+        .iter()
+        .map(|share| create_img(share, watermark).ok_or(Error::InvalidImage))
+        .collect()
+}
+
+// Recover the payload from at least `threshold` shares of one split, in any
+// order. Extra shares are checked against the others: shares from different
+// splits, or that were altered, fail with `Error::InconsistentShares`.
+pub fn combine_shares(shares: &[&str]) -> Result<SecretBytes, Error> {
+    let mut decoded = Vec::with_capacity(shares.len());
+    for share in shares {
+        decoded.push(Zeroizing::new(STANDARD.decode(share)?));
+    }
+    let parsed = decoded
+        .iter()
+        .map(|share| Share::parse(share))
+        .collect::<Result<Vec<Share>, Error>>()?;
+
+    let first = parsed.first().ok_or(Error::NotEnoughShares)?;
+    let mut points: Vec<(u8, &[u8])> = Vec::with_capacity(parsed.len());
+    for share in &parsed {
+        if share.prefix != first.prefix || share.envelope != first.envelope {
+            return Err(Error::InconsistentShares);
+        }
+        match points.iter().find(|(x, _)| *x == share.index) {
+            Some((_, y)) if *y != share.key_share => return Err(Error::InconsistentShares),
+            Some(_) => {}
+            None => points.push((share.index, share.key_share)),
+        }
+    }
+
+    let threshold = first.prefix[MAGIC.len() + 1] as usize;
+    if points.len() < threshold {
+        return Err(Error::NotEnoughShares);
+    }
+    let (basis, extra) = points.split_at(threshold);
+    for (x, y) in extra {
+        if interpolate(basis, *x).as_slice() != *y {
+            return Err(Error::InconsistentShares);
+        }
+    }
+
+    let key = Key::from_vec(interpolate(basis, 0).to_vec());
+    let envelope = envelope::parse(first.envelope)?;
+    match open_envelope_with_context(&envelope, &key, first.prefix) {
+        // With exactly `threshold` shares a bad one only shows as a wrong key
+        Err(Error::AuthenticationFailed) => Err(Error::InconsistentShares),
+        result => result,
+    }
+}
+
+// `combine_shares` for images from `split_to_images`
+pub fn combine_images(images: &[&str]) -> Result<SecretBytes, Error> {
+    let mut shares = Vec::with_capacity(images.len());
+    for image in images {
+        shares.push(decode_image_and_extract_text(image).ok_or(Error::InvalidImage)?);
+    }
+    combine_shares(&shares.iter().map(String::as_str).collect::<Vec<_>>())
+}
+
+struct Share<'a> {
+    // Magic, version, threshold and group id
+    prefix: &'a [u8],
+    index: u8,
+    key_share: &'a [u8],
+    envelope: &'a [u8],
+}
+
+impl<'a> Share<'a> {
+    fn parse(data: &'a [u8]) -> Result<Share<'a>, Error> {
+        let mut reader = Reader::new(data);
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(Error::Malformed);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if reader.u8()? < 2 {
+            return Err(Error::Malformed);
+        }
+        reader.bytes(GROUP_ID_LEN)?;
+        let prefix = &data[..reader.position()];
+
+        let index = reader.u8()?;
+        if index == 0 {
+            return Err(Error::Malformed);
+        }
+        let key_share = reader.bytes(SUITE.key_len())?;
+        let envelope = reader.bytes(data.len() - reader.position())?;
+        Ok(Share {
+            prefix,
+            index,
+            key_share,
+            envelope,
+        })
+    }
+}
+
+// Shares of every byte of `secret` for x = 1 to `shares`
+fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Zeroizing<Vec<u8>>>, Error> {
+    let mut coefficients = Zeroizing::new(vec![0; secret.len() * (threshold as usize - 1)]);
+    backend::random_bytes(&mut coefficients)?;
+
+    let mut out = Vec::with_capacity(shares as usize);
+    for x in 1..=shares {
+        let mut share = Zeroizing::new(Vec::with_capacity(secret.len()));
+        for (constant, terms) in secret
+            .iter()
+            .zip(coefficients.chunks(threshold as usize - 1))
+        {
+            // Horner's rule from the highest coefficient down to the linear
+            // one, then the constant term
+            let y = terms
+                .iter()
+                .rev()
+                .fold(0, |acc, term| gf_mul(acc ^ term, x));
+            share.push(y ^ constant);
+        }
+        out.push(share);
+    }
+    Ok(out)
+}
+
+// Lagrange interpolation of the polynomials through `points`, evaluated at `x`
+fn interpolate(points: &[(u8, &[u8])], x: u8) -> Zeroizing<Vec<u8>> {
+    let len = points[0].1.len();
+    let mut out = Zeroizing::new(vec![0; len]);
+    for (j, (xj, yj)) in points.iter().enumerate() {
+        // Basis polynomial j at x: the product of (x - xm) / (xj - xm), where
+        // subtraction in GF(2^8) is xor
+        let mut basis = 1;
+        for (m, (xm, _)) in points.iter().enumerate() {
+            if m != j {
+                basis = gf_mul(basis, gf_mul(x ^ xm, gf_inv(xj ^ xm)));
+            }
+        }
+        for (byte, y) in out.iter_mut().zip(yj.iter()) {
+            *byte ^= gf_mul(basis, *y);
+        }
+    }
+    out
+}
+
+// Multiplication in GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1,
+// without branches or table lookups that depend on the operands
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = a >> 7;
+        a = (a << 1) ^ (0x1b & carry.wrapping_neg());
+        b >>= 1;
+    }
+    product
+}
+
+// a^254, which is a^-1 for a != 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
@@ -0,0 +1,152 @@
+use crate::pubkey::key_id;
+use crate::Error;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey, Private, Public};
+use openssl::sign::{Signer, Verifier};
+
+const ED25519_ID: u8 = 1;
+const ECDSA_P256_SHA256_ID: u8 = 2;
+
+// A key that signs envelopes, proving who issued an image. Ed25519 by
+// default, ECDSA P-256 with SHA-256 where a NIST curve is required.
+#[derive(Clone)]
+pub struct SigningKey {
+    pkey: PKey<Private>,
+}
+
+#[derive(Clone)]
+pub struct VerifyingKey {
+    pkey: PKey<Public>,
+}
+
+impl SigningKey {
+    pub fn generate_ed25519() -> Result<SigningKey, Error> {
+        Ok(SigningKey {
+            pkey: PKey::generate_ed25519()?,
+        })
+    }
+
+    pub fn generate_p256() -> Result<SigningKey, Error> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Ok(SigningKey {
+            pkey: PKey::from_ec_key(EcKey::generate(&group)?)?,
+        })
+    }
+
+    pub fn from_pem(pem: &[u8]) -> Result<SigningKey, Error> {
+        let pkey = PKey::private_key_from_pem(pem)?;
+        algorithm_id(&pkey)?;
+        Ok(SigningKey { pkey })
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<SigningKey, Error> {
+        let pkey = PKey::private_key_from_der(der)?;
+        algorithm_id(&pkey)?;
+        Ok(SigningKey { pkey })
+    }
+
+    // Unencrypted PKCS#8 PEM
+    pub fn to_pem(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.private_key_to_pem_pkcs8()?)
+    }
+
+    // Unencrypted PKCS#8 DER
+    pub fn to_der(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.private_key_to_pkcs8()?)
+    }
+
+    pub fn verifying_key(&self) -> Result<VerifyingKey, Error> {
+        VerifyingKey::from_der(&self.pkey.public_key_to_der()?)
+    }
+
+    // Identifies the signer, computed like `PublicKey::key_id`
+    pub fn key_id(&self) -> Result<Vec<u8>, Error> {
+        key_id(&self.pkey.public_key_to_der()?)
+    }
+
+    pub(crate) fn algorithm_id(&self) -> u8 {
+        // Checked when the key was created or imported
+        algorithm_id(&self.pkey).unwrap()
+    }
+
+    pub(crate) fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.pkey.id() {
+            Id::ED25519 => {
+                let mut signer = Signer::new_without_digest(&self.pkey)?;
+                Ok(signer.sign_oneshot_to_vec(data)?)
+            }
+            _ => {
+                let mut signer = Signer::new(MessageDigest::sha256(), &self.pkey)?;
+                signer.update(data)?;
+                Ok(signer.sign_to_vec()?)
+            }
+        }
+    }
+}
+
+impl VerifyingKey {
+    pub fn from_pem(pem: &[u8]) -> Result<VerifyingKey, Error> {
+        let pkey = PKey::public_key_from_pem(pem)?;
+        algorithm_id(&pkey)?;
+        Ok(VerifyingKey { pkey })
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<VerifyingKey, Error> {
+        let pkey = PKey::public_key_from_der(der)?;
+        algorithm_id(&pkey)?;
+        Ok(VerifyingKey { pkey })
+    }
+
+    // SubjectPublicKeyInfo PEM
+    pub fn to_pem(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.public_key_to_pem()?)
+    }
+
+    // SubjectPublicKeyInfo DER
+    pub fn to_der(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.pkey.public_key_to_der()?)
+    }
+
+    pub fn key_id(&self) -> Result<Vec<u8>, Error> {
+        key_id(&self.pkey.public_key_to_der()?)
+    }
+
+    pub(crate) fn verify(&self, algorithm: u8, data: &[u8], signature: &[u8]) -> Result<(), Error> {
+        if algorithm != algorithm_id(&self.pkey)? {
+            return Err(Error::InvalidSignature);
+        }
+        let valid = match self.pkey.id() {
+            Id::ED25519 => {
+                let mut verifier = Verifier::new_without_digest(&self.pkey)?;
+                verifier.verify_oneshot(signature, data)
+            }
+            _ => {
+                let mut verifier = Verifier::new(MessageDigest::sha256(), &self.pkey)?;
+                verifier.update(data)?;
+                verifier.verify(signature)
+            }
+        };
+
+        // OpenSSL reports some malformed signatures as errors rather than false
+        if !valid.unwrap_or(false) {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+fn algorithm_id<T: HasPublic>(pkey: &PKey<T>) -> Result<u8, Error> {
+    match pkey.id() {
+        Id::ED25519 => Ok(ED25519_ID),
+        Id::EC => {
+            let curve = pkey.ec_key()?.group().curve_name();
+            if curve != Some(Nid::X9_62_PRIME256V1) {
+                return Err(Error::UnsupportedKeyType);
+            }
+            Ok(ECDSA_P256_SHA256_ID)
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
@@ -0,0 +1,267 @@
+use crate::backend::{self, TAG_LEN};
+use crate::envelope::{self, Header, KeySource, Reader, FLAG_STREAM};
+use crate::hkdf::hkdf_sha256;
+use crate::{CipherSuite, Error, Key};
+use std::io::{self, ErrorKind, Read, Write};
+use zeroize::{Zeroize, Zeroizing};
+
+const SALT_LEN: usize = 16;
+const CHUNK_SIZE: usize = 64 * 1024;
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+// Envelope header with a direct key, then the salt and chunk size
+const HEADER_LEN: usize = 8 + SALT_LEN + 4;
+
+// Streamed layout, for payloads too large to encrypt in one piece:
+//
+//   header      8 bytes  envelope header with the stream flag and a direct key
+//   salt       16 bytes  random, the stream key is HKDF-SHA256 of the key and
+//                        this salt, so nonces never repeat across streams
+//   chunk size  4 bytes  plaintext bytes per chunk
+//   chunks               each chunk sealed with the suite's AEAD. All but the
+//                        last hold exactly chunk size bytes; the last holds up
+//                        to chunk size and is empty only for an empty stream.
+//
+// This is the STREAM construction: the nonce of chunk i is the counter i in
+// the first 11 bytes followed by a byte that is 1 for the last chunk and 0
+// otherwise. Dropping, reordering or truncating chunks makes a tag fail. Every
+// chunk authenticates the 28 byte stream header as associated data.
+
+// Encrypts everything written to it to `writer`. `finish` must be called to
+// write the last chunk, a stream that was only dropped fails to decrypt.
+pub struct StreamEncryptor<W: Write> {
+    writer: W,
+    suite: CipherSuite,
+    key: Key,
+    header: Vec<u8>,
+    counter: u32,
+    // Plaintext of the chunk being filled, zeroized once it is sealed
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> StreamEncryptor<W> {
+    // Only the AEAD suites can be streamed
+    pub fn new(mut writer: W, key: &Key, suite: CipherSuite) -> Result<StreamEncryptor<W>, Error> {
+        if !suite.is_aead() {
+            return Err(Error::UnsupportedSuite);
+        }
+        key.check_len(suite.key_len())?;
+
+        let mut salt = [0; SALT_LEN];
+        backend::random_bytes(&mut salt)?;
+
+        let mut header = Header {
+            suite,
+            key_source: KeySource::Direct,
+        }
+        .to_bytes(FLAG_STREAM);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&(CHUNK_SIZE as u32).to_be_bytes());
+        writer.write_all(&header)?;
+
+        Ok(StreamEncryptor {
+            writer,
+            suite,
+            key: stream_key(key, &salt, suite)?,
+            header,
+            counter: 0,
+            buffer: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE)),
+        })
+    }
+
+    // Write the last chunk and hand back the writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.seal_chunk(true)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> Result<(), Error> {
+        let nonce = chunk_nonce(self.counter, last);
+        let sealed = self
+            .suite
+            .seal_with_nonce(&self.key, &nonce, &self.buffer, &self.header)?;
+        self.writer.write_all(&sealed)?;
+        self.buffer.zeroize();
+
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "stream too long"))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for StreamEncryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full chunk is only sealed once more data arrives, as it might
+        // otherwise have been the last one
+        if self.buffer.len() == CHUNK_SIZE && !buf.is_empty() {
+            self.seal_chunk(false).map_err(to_io)?;
+        }
+        let len = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Decrypts a stream from `StreamEncryptor` as it is read. Reads fail with an
+// error wrapping `Error::AuthenticationFailed` as soon as a chunk does not
+// verify, so data read before that comes from chunks that did.
+pub struct StreamDecryptor<R: Read> {
+    reader: R,
+    suite: CipherSuite,
+    key: Key,
+    header: Vec<u8>,
+    chunk_size: usize,
+    counter: u32,
+    // Sealed bytes read ahead, to tell whether a chunk is the last one
+    sealed: Vec<u8>,
+    plaintext: Zeroizing<Vec<u8>>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> StreamDecryptor<R> {
+    pub fn new(mut reader: R, key: &Key) -> Result<StreamDecryptor<R>, Error> {
+        let mut header = vec![0; HEADER_LEN];
+        reader
+            .read_exact(&mut header)
+            .map_err(|err| match err.kind() {
+                ErrorKind::UnexpectedEof => Error::Malformed,
+                _ => Error::Io(err),
+            })?;
+
+        let mut fields = Reader::new(&header);
+        let (parsed, flags) = envelope::parse_header(&mut fields)?;
+        if flags != FLAG_STREAM || !matches!(parsed.key_source, KeySource::Direct) {
+            return Err(Error::Malformed);
+        }
+        if !parsed.suite.is_aead() {
+            return Err(Error::UnsupportedSuite);
+        }
+        let salt = fields.bytes(SALT_LEN)?;
+        let chunk_size = fields.u32()? as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::Malformed);
+        }
+        key.check_len(parsed.suite.key_len())?;
+
+        Ok(StreamDecryptor {
+            reader,
+            suite: parsed.suite,
+            key: stream_key(key, salt, parsed.suite)?,
+            header,
+            chunk_size,
+            counter: 0,
+            sealed: Vec::with_capacity(chunk_size + TAG_LEN + 1),
+            plaintext: Zeroizing::new(Vec::new()),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    fn open_chunk(&mut self) -> Result<(), Error> {
+        // Read one byte past a full chunk: if it is there, more chunks follow
+        let sealed_len = self.chunk_size + TAG_LEN;
+        while self.sealed.len() <= sealed_len {
+            let start = self.sealed.len();
+            self.sealed.resize(sealed_len + 1, 0);
+            match self.reader.read(&mut self.sealed[start..]) {
+                Ok(0) => {
+                    self.sealed.truncate(start);
+                    break;
+                }
+                Ok(n) => self.sealed.truncate(start + n),
+                Err(err) if err.kind() == ErrorKind::Interrupted => self.sealed.truncate(start),
+                Err(err) => {
+                    self.sealed.truncate(start);
+                    return Err(Error::Io(err));
+                }
+            }
+        }
+
+        let last = self.sealed.len() <= sealed_len;
+        let chunk_len = self.sealed.len().min(sealed_len);
+        let nonce = chunk_nonce(self.counter, last);
+        self.plaintext = Zeroizing::new(self.suite.open_with_nonce(
+            &self.key,
+            &nonce,
+            &self.sealed[..chunk_len],
+            &self.header,
+        )?);
+        self.sealed.drain(..chunk_len);
+        self.pos = 0;
+
+        self.done = last;
+        self.counter = self.counter.checked_add(1).ok_or(Error::Malformed)?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.open_chunk().map_err(to_io)?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..len].copy_from_slice(&self.plaintext[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+// Encrypt all of `reader` to `writer`, returning the number of plaintext bytes
+pub fn encrypt_stream<R: Read, W: Write>(
+    reader: &mut R,
+    writer: W,
+    key: &Key,
+    suite: CipherSuite,
+) -> Result<u64, Error> {
+    let mut encryptor = StreamEncryptor::new(writer, key, suite)?;
+    let len = io::copy(reader, &mut encryptor)?;
+    encryptor.finish()?;
+    Ok(len)
+}
+
+// Decrypt all of `reader` to `writer`, returning the number of plaintext
+// bytes. On error, `writer` may already hold the chunks that verified.
+pub fn decrypt_stream<R: Read, W: Write>(
+    reader: R,
+    writer: &mut W,
+    key: &Key,
+) -> Result<u64, Error> {
+    let mut decryptor = StreamDecryptor::new(reader, key)?;
+    Ok(io::copy(&mut decryptor, writer)?)
+}
+
+fn stream_key(key: &Key, salt: &[u8], suite: CipherSuite) -> Result<Key, Error> {
+    hkdf_sha256(
+        key.as_bytes(),
+        salt,
+        b"colorsquares stream",
+        suite.key_len(),
+    )
+}
+
+fn chunk_nonce(counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn to_io(err: Error) -> io::Error {
+    match err {
+        Error::Io(err) => err,
+        err => io::Error::new(ErrorKind::InvalidData, err),
+    }
+}
@@ -0,0 +1,189 @@
+use crate::backend::{self, Aead, TAG_LEN};
+use crate::hkdf::hkdf_sha256;
+use crate::{calculate_hmac, Error, Key};
+
+const HMAC_LEN: usize = 32;
+
+const NO_MAC_ID: u8 = 0;
+const HMAC_SHA256_ID: u8 = 1;
+
+// The symmetric constructions a payload can be encrypted with. The GCM and
+// ChaCha20-Poly1305 suites are authenticated encryption: the nonce and the
+// ciphertext are both covered by the tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CipherSuite {
+    // AES-128-CBC with HMAC-SHA256 over the header, IV and ciphertext. The
+    // cipher and MAC keys are derived separately from the key with HKDF.
+    #[default]
+    Aes128CbcHmacSha256,
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    // Identifiers written to the envelope header. Never reuse a number.
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 => 1,
+            CipherSuite::Aes128Gcm => 2,
+            CipherSuite::Aes256Gcm => 3,
+            CipherSuite::ChaCha20Poly1305 => 4,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<CipherSuite, Error> {
+        match id {
+            1 => Ok(CipherSuite::Aes128CbcHmacSha256),
+            2 => Ok(CipherSuite::Aes128Gcm),
+            3 => Ok(CipherSuite::Aes256Gcm),
+            4 => Ok(CipherSuite::ChaCha20Poly1305),
+            _ => Err(Error::UnknownAlgorithm(id)),
+        }
+    }
+
+    pub(crate) fn mac_id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 => HMAC_SHA256_ID,
+            _ => NO_MAC_ID,
+        }
+    }
+
+    // Only called for the AEAD suites
+    fn aead(&self) -> Aead {
+        match self {
+            CipherSuite::Aes128Gcm => Aead::Aes128Gcm,
+            CipherSuite::Aes256Gcm => Aead::Aes256Gcm,
+            CipherSuite::ChaCha20Poly1305 => Aead::ChaCha20Poly1305,
+            CipherSuite::Aes128CbcHmacSha256 => unreachable!("CBC is not an AEAD"),
+        }
+    }
+
+    pub fn key_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 | CipherSuite::Aes128Gcm => 16,
+            CipherSuite::Aes256Gcm | CipherSuite::ChaCha20Poly1305 => 32,
+        }
+    }
+
+    pub(crate) fn nonce_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 => 16,
+            _ => 12,
+        }
+    }
+
+    // Encrypt `plaintext`, returning the nonce/IV followed by the sealed data.
+    // AEAD suites also authenticate `aad`.
+    pub(crate) fn seal(&self, key: &Key, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        key.check_len(self.key_len())?;
+
+        // Use a fresh random IV for every message
+        let mut iv = vec![0; self.nonce_len()];
+        backend::random_bytes(&mut iv)?;
+
+        let mut result = iv;
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 => {
+                let (enc_key, mac_key) = cbc_subkeys(key)?;
+                let ciphertext =
+                    backend::aes_128_cbc_encrypt(enc_key.as_bytes(), &result, plaintext)?;
+                let hmac =
+                    calculate_hmac(&[aad, &result, &ciphertext].concat(), mac_key.as_bytes());
+
+                // IV | HMAC | ciphertext
+                result.extend_from_slice(&hmac);
+                result.extend_from_slice(&ciphertext);
+            }
+            _ => {
+                // nonce | ciphertext | tag
+                let sealed = self.seal_with_nonce(key, &result, plaintext, aad)?;
+                result.extend_from_slice(&sealed);
+            }
+        }
+        Ok(result)
+    }
+
+    // Reverse of `seal`
+    pub(crate) fn open(&self, key: &Key, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        key.check_len(self.key_len())?;
+
+        match self {
+            CipherSuite::Aes128CbcHmacSha256 => {
+                if data.len() < self.nonce_len() + HMAC_LEN {
+                    return Err(Error::Malformed);
+                }
+
+                // Extract the IV, HMAC, and ciphertext from the result
+                let (iv, rest) = data.split_at(self.nonce_len());
+                let (hmac, ciphertext) = rest.split_at(HMAC_LEN);
+
+                // Verify the HMAC over header, IV and ciphertext
+                let (enc_key, mac_key) = cbc_subkeys(key)?;
+                verify_hmac(&[aad, iv, ciphertext].concat(), mac_key.as_bytes(), hmac)?;
+
+                backend::aes_128_cbc_decrypt(enc_key.as_bytes(), iv, ciphertext)
+            }
+            _ => {
+                if data.len() < self.nonce_len() + TAG_LEN {
+                    return Err(Error::Malformed);
+                }
+
+                let (nonce, sealed) = data.split_at(self.nonce_len());
+                self.open_with_nonce(key, nonce, sealed, aad)
+            }
+        }
+    }
+
+    pub(crate) fn is_aead(&self) -> bool {
+        self.mac_id() == NO_MAC_ID
+    }
+
+    // AEAD suites only: encrypt under a caller-chosen nonce, returning the
+    // ciphertext followed by the tag. The caller must never repeat a nonce.
+    pub(crate) fn seal_with_nonce(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        plaintext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        key.check_len(self.key_len())?;
+        backend::aead_seal(self.aead(), key.as_bytes(), nonce, aad, plaintext)
+    }
+
+    // Reverse of `seal_with_nonce`
+    pub(crate) fn open_with_nonce(
+        &self,
+        key: &Key,
+        nonce: &[u8],
+        sealed: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        key.check_len(self.key_len())?;
+        if sealed.len() < TAG_LEN {
+            return Err(Error::Malformed);
+        }
+
+        backend::aead_open(self.aead(), key.as_bytes(), nonce, aad, sealed)
+    }
+}
+
+// Independent AES and HMAC keys for the CBC suite, so the same key material is
+// never used for both
+fn cbc_subkeys(key: &Key) -> Result<(Key, Key), Error> {
+    let enc_key = hkdf_sha256(key.as_bytes(), &[], b"colorsquares cbc encryption", 16)?;
+    let mac_key = hkdf_sha256(key.as_bytes(), &[], b"colorsquares cbc authentication", 32)?;
+    Ok((enc_key, mac_key))
+}
+
+pub(crate) fn verify_hmac(data: &[u8], key: &[u8], hmac: &[u8]) -> Result<(), Error> {
+    let hmac_calculated = calculate_hmac(data, key);
+
+    // Compare in constant time so the check does not leak how many leading
+    // bytes of a forged MAC were right
+    if !backend::constant_time_eq(&hmac_calculated, hmac) {
+        return Err(Error::AuthenticationFailed); // the data may have been tampered with
+    }
+    Ok(())
+}
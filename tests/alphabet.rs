@@ -0,0 +1,194 @@
+mod common;
+
+use colorsquares::{
+    create_img, create_img_with_alphabet, decode_image_and_extract_text,
+    decode_image_with_alphabet, encrypt_with, get_color, numbers_to_letter, ColorAlphabet, Error,
+};
+use common::key;
+use proptest::prelude::*;
+
+const BASE64: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+#[test]
+fn standard_alphabet_covers_base64() {
+    let alphabet = ColorAlphabet::standard();
+    assert_eq!(alphabet.len(), 65);
+    for symbol in BASE64.chars() {
+        let color = alphabet.color(symbol).unwrap();
+        assert_eq!(alphabet.symbol(color), Some(symbol));
+        assert_eq!(get_color(symbol), Some(color));
+        assert_eq!(numbers_to_letter(color.0, color.1, color.2), Some(symbol));
+    }
+    assert_eq!(alphabet.color('*'), None);
+}
+
+#[test]
+fn repeated_symbols_or_colors_are_rejected() {
+    assert!(matches!(
+        ColorAlphabet::new(&[('a', (1, 2, 3)), ('a', (4, 5, 6))]),
+        Err(Error::InvalidAlphabet)
+    ));
+    assert!(matches!(
+        ColorAlphabet::new(&[('a', (1, 2, 3)), ('b', (1, 2, 3))]),
+        Err(Error::InvalidAlphabet)
+    ));
+    assert!(ColorAlphabet::new(&[('a', (1, 2, 3)), ('b', (4, 5, 6))]).is_ok());
+}
+
+// A palette far from the standard one: every symbol of BASE64 on a grid of
+// saturated colors
+fn grid_palette() -> ColorAlphabet {
+    let levels = [0, 64, 128, 192, 255];
+    let entries: Vec<(char, (u8, u8, u8))> = BASE64
+        .chars()
+        .enumerate()
+        .map(|(i, symbol)| (symbol, (levels[i / 25], levels[i / 5 % 5], levels[i % 5])))
+        .collect();
+    ColorAlphabet::new(&entries).unwrap()
+}
+
+#[test]
+fn palettes_load_from_toml_and_json() {
+    let toml = r##"
+        min_distance = 20.0
+
+        [colors]
+        A = "#0B3D91"
+        B = [250, 70, 22]
+        "+" = "#ffffff"
+    "##;
+    let json = r##"{"min_distance": 20.0, "colors": {"A": "#0B3D91", "B": [250, 70, 22], "+": "#ffffff"}}"##;
+
+    for alphabet in [
+        ColorAlphabet::from_toml(toml).unwrap(),
+        ColorAlphabet::from_json(json).unwrap(),
+    ] {
+        assert_eq!(
+            alphabet.entries(),
+            [
+                ('A', (11, 61, 145)),
+                ('B', (250, 70, 22)),
+                ('+', (255, 255, 255))
+            ]
+        );
+    }
+}
+
+#[test]
+fn palettes_with_lookalike_colors_are_rejected() {
+    let (first, second, distance) = ColorAlphabet::standard().closest_pair().unwrap();
+    assert_eq!((first, second), ('G', 'X'));
+    assert!(distance < 2.0);
+
+    let entries = [('A', (0, 0, 0)), ('B', (200, 0, 0)), ('C', (201, 1, 0))];
+    assert!(ColorAlphabet::new(&entries).is_ok());
+    assert!(matches!(
+        ColorAlphabet::with_min_distance(&entries, 2.0),
+        Err(Error::ColorsTooClose {
+            first: 'B',
+            second: 'C',
+            ..
+        })
+    ));
+    assert!(ColorAlphabet::with_min_distance(&entries[..2], 2.0).is_ok());
+
+    // Files without a minimum use the default
+    assert!(matches!(
+        ColorAlphabet::from_toml("[colors]\nA = [10, 10, 10]\nB = [12, 12, 12]"),
+        Err(Error::ColorsTooClose { .. })
+    ));
+    assert!(ColorAlphabet::from_toml(
+        "min_distance = 0.0\n[colors]\nA = [10, 10, 10]\nB = [12, 12, 12]"
+    )
+    .is_ok());
+}
+
+#[test]
+fn invalid_palette_files_are_rejected() {
+    for json in [
+        // The same symbol twice, or the same color
+        r#"{"colors": {"A": [0, 0, 0], "A": [255, 255, 255]}}"#,
+        r##"{"colors": {"A": [0, 0, 0], "B": "#000000"}}"##,
+        // Not one symbol, not a color
+        r#"{"colors": {"AB": [0, 0, 0]}}"#,
+        r#"{"colors": {"A": [0, 0, 256]}}"#,
+        r#"{"colors": {"A": "000000"}}"#,
+        r##"{"colors": {"A": "#00000g"}}"##,
+        r#"{"colors": {"A": [0, 0, 0]}, "extra": 1}"#,
+    ] {
+        assert!(
+            matches!(ColorAlphabet::from_json(json), Err(Error::InvalidAlphabet)),
+            "{}",
+            json
+        );
+    }
+}
+
+#[test]
+fn images_round_trip_with_a_custom_palette() {
+    let alphabet = grid_palette();
+    let encrypted = encrypt_with("DataYouWantToEncrypt", &key()).unwrap();
+
+    let image = create_img_with_alphabet(&encrypted, "bitcoin", &alphabet).unwrap();
+    assert_eq!(
+        decode_image_with_alphabet(&image, &alphabet).unwrap(),
+        encrypted
+    );
+    // Drawn in other colors than the standard alphabet
+    assert_ne!(decode_image_and_extract_text(&image), Some(encrypted));
+
+    // Symbols the palette has no color for
+    assert_eq!(create_img_with_alphabet("AB*", "bitcoin", &alphabet), None);
+}
+
+#[test]
+fn palette_files_are_read_by_extension() {
+    let dir = std::env::temp_dir();
+    let toml_path = dir.join(format!("colorsquares-palette-{}.toml", std::process::id()));
+    let json_path = toml_path.with_extension("json");
+    std::fs::write(&toml_path, "[colors]\nA = [0, 0, 0]\nB = [255, 255, 255]\n").unwrap();
+    std::fs::write(&json_path, r#"{"colors": {"A": [0, 0, 0]}}"#).unwrap();
+
+    let from_toml = ColorAlphabet::open(&toml_path);
+    let from_json = ColorAlphabet::open(&json_path);
+    std::fs::remove_file(&toml_path).unwrap();
+    std::fs::remove_file(&json_path).unwrap();
+
+    assert_eq!(from_toml.unwrap().len(), 2);
+    assert_eq!(from_json.unwrap().len(), 1);
+    assert!(matches!(
+        ColorAlphabet::open(dir.join("missing.toml")),
+        Err(Error::Io(_))
+    ));
+}
+
+proptest! {
+    #[test]
+    fn every_symbol_decodes_to_itself(
+        symbol in proptest::sample::select(ColorAlphabet::standard().symbols().collect::<Vec<_>>())
+    ) {
+        let alphabet = ColorAlphabet::standard();
+        let color = alphabet.color(symbol).unwrap();
+        prop_assert_eq!(alphabet.symbol(color), Some(symbol));
+    }
+
+    // Without a watermark, which needs an image at least 32 pixels wide
+    #[test]
+    fn images_decode_to_their_text(text in "[A-Za-z0-9+/=]{1,48}") {
+        let image = create_img(&text, "none").unwrap();
+        prop_assert_eq!(decode_image_and_extract_text(&image).unwrap(), text);
+    }
+
+    #[test]
+    fn watermarked_images_decode_to_their_text(text in "[A-Za-z0-9+/=]{32,64}") {
+        let image = create_img(&text, "bitcoin").unwrap();
+        prop_assert_eq!(decode_image_and_extract_text(&image).unwrap(), text);
+    }
+
+    #[test]
+    fn custom_palette_images_decode_to_their_text(text in "[A-Za-z0-9+/=]{1,48}") {
+        let alphabet = grid_palette();
+        let image = create_img_with_alphabet(&text, "none", &alphabet).unwrap();
+        prop_assert_eq!(decode_image_with_alphabet(&image, &alphabet).unwrap(), text);
+    }
+}
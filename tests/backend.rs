@@ -0,0 +1,64 @@
+// Envelopes written by each crypto backend. Both backends must open all of
+// them: run with the default features and with
+// `--no-default-features --features rustcrypto`.
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{decrypt_stream, decrypt_with};
+use common::{key, key32};
+
+const OPENSSL_ENVELOPES: [(&str, bool); 4] = [
+    ("Q1NRAQEBAAAAAABQD0/SXx0grthdkdVzoO1y9viFMtCF15JPnVvRTLoVm+q94X7OOOBRaX28cmtakisAYaKiFuoTTmcaxKoQm+btjkbCW/L/kX1evMzi+Wn9bQo=", false),
+    ("Q1NRAQIAAAAAAAA6OcK1iUj/5ySrkSWPMbudbYvnSyYAJZECxVKivtpMQanhYitVoOYJuvXEeehFAxEhLAgEn5gfHTadZw==", false),
+    ("Q1NRAQMAAAAAAAA6O4Lv/aiKmZt0UJ0s9sNq0QgXPLEy7hzz6tC4ETuL78M2qIzHSyC5FSpc4n27GrCmQKPKCRqr5U9KFg==", true),
+    ("Q1NRAQQAAAAAAAA6LG3nzgT0hWZufdL8ZSdWV1GibbNmJyAUQTNKA2IDm+W50cZAGqrDcoYeB2GuA/VEHtBA7aPiqaJvEg==", true),
+];
+
+const RUSTCRYPTO_ENVELOPES: [(&str, bool); 4] = [
+    ("Q1NRAQEBAAAAAABgmo7KGlsTgNmgLFOsZEUg8XU2vH4rxQTxu/f4a6nWs5GETHgjyf+D5inkfarRAeiADRTTpm2s7OWvQ731DPRdvyyeTas3QhID1KaSpjdJ3htQGb55bhzsFptpMwCOkme/", false),
+    ("Q1NRAQIAAAAAAAA9a3T4MQR56hlSfoVPkUCrtQ0xCezII2eng9zOLd6VzwoVAWkjSJA9P2m3ZHKRosG2Ys8c/0o4/LCjS2fOCw==", false),
+    ("Q1NRAQMAAAAAAAA9qp6Dz9PuGKgZpS89qgj1SP07QY1FlB17ouYWXWT851gYze3Aexg18vRac1WI6r+Q0wevWOiveBMwdESQjQ==", true),
+    ("Q1NRAQQAAAAAAAA9gZ6Atv/1F2VQVMOD1JEQ7ZZlo0HYHMj7seS26rhoTONflKskbgWcJNlFJhOm4PjkgG0VrOq3pgwUdPplhw==", true),
+];
+
+fn open_stream(encoded: &str) -> Vec<u8> {
+    let data = STANDARD.decode(encoded).unwrap();
+    let mut out = Vec::new();
+    decrypt_stream(&data[..], &mut out, &key32()).unwrap();
+    out
+}
+
+#[test]
+fn opens_envelopes_written_with_openssl() {
+    for (envelope, long_key) in OPENSSL_ENVELOPES {
+        let key = if long_key { key32() } else { key() };
+        assert_eq!(
+            decrypt_with(envelope, &key).unwrap().as_str(),
+            "written by the openssl backend"
+        );
+    }
+}
+
+#[test]
+fn opens_envelopes_written_with_rustcrypto() {
+    for (envelope, long_key) in RUSTCRYPTO_ENVELOPES {
+        let key = if long_key { key32() } else { key() };
+        assert_eq!(
+            decrypt_with(envelope, &key).unwrap().as_str(),
+            "written by the rustcrypto backend"
+        );
+    }
+}
+
+#[test]
+fn opens_streams_written_with_either_backend() {
+    assert_eq!(
+        open_stream("Q1NRAQQAAAFwqVMOkXPHfJGU6KFLjyZaAAEAAPB9m1C7lWLafJVnEPEYJ8y5z1yMXl7XizePHtAdodrQX+qUuD4cSd8/IfOtIVoN"),
+        b"streamed by the openssl backend"
+    );
+    assert_eq!(
+        open_stream("Q1NRAQQAAAHbUwqB4HKu7IinxbACVzRuAAEAAEZG28sk1TwjnsgeOpnJAdf+7A6Vy/PjHYfChA752dZKslMNa5/BM9tsR/n/Z+2eK0pM"),
+        b"streamed by the rustcrypto backend"
+    );
+}
@@ -0,0 +1,14 @@
+// Fixtures shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use colorsquares::Key;
+
+// A key for the default suite and the other 128-bit ones
+pub fn key() -> Key {
+    Key::new(b"0123456789abcdef")
+}
+
+// A key for the 256-bit suites
+pub fn key32() -> Key {
+    Key::new(b"0123456789abcdef0123456789abcdef")
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    create_img, create_img_with_alphabet, decode_image_and_extract_text, decode_image_tolerant,
+    encrypt_with, ColorAlphabet, DecodeOptions, Error,
+};
+use common::key;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageEncoder, Rgba};
+
+fn load(encoded_image: &str) -> DynamicImage {
+    image::load_from_memory(&STANDARD.decode(encoded_image).unwrap()).unwrap()
+}
+
+fn png(img: &DynamicImage) -> String {
+    let mut buf = Vec::new();
+    let rgba = img.to_rgba8();
+    PngEncoder::new(&mut buf)
+        .write_image(&rgba, rgba.width(), rgba.height(), image::ColorType::Rgba8)
+        .unwrap();
+    STANDARD.encode(buf)
+}
+
+// What a chat app does to an image: scaled and saved as a JPEG
+fn scale_and_compress(encoded_image: &str, scale: u32, quality: u8) -> String {
+    let img = load(encoded_image);
+    let img = img.resize(
+        img.width() * scale,
+        img.height() * scale,
+        FilterType::Triangle,
+    );
+    let rgb = img.to_rgb8();
+    let mut buf = Vec::new();
+    JpegEncoder::new_with_quality(&mut buf, quality)
+        .write_image(&rgb, rgb.width(), rgb.height(), image::ColorType::Rgb8)
+        .unwrap();
+    STANDARD.encode(buf)
+}
+
+#[test]
+fn exact_images_decode_with_full_confidence() {
+    let encrypted = encrypt_with("DataYouWantToEncrypt", &key()).unwrap();
+    let image = create_img(&encrypted, "bitcoin").unwrap();
+
+    let decoded =
+        decode_image_tolerant(&image, ColorAlphabet::standard(), &DecodeOptions::default())
+            .unwrap();
+    assert_eq!(decoded.text(), encrypted);
+    assert_eq!(Some(decoded.text()), decode_image_and_extract_text(&image));
+    assert_eq!(decoded.min_confidence(), 1.0);
+    assert!(decoded.symbols.iter().all(|symbol| symbol.distance == 0.0));
+}
+
+// Sixteen well separated colors, enough for hex
+fn hex_palette() -> ColorAlphabet {
+    let colors = [
+        (0, 0, 0),
+        (255, 255, 255),
+        (230, 25, 75),
+        (60, 180, 75),
+        (255, 225, 25),
+        (0, 130, 200),
+        (245, 130, 48),
+        (145, 30, 180),
+        (70, 240, 240),
+        (240, 50, 230),
+        (128, 128, 128),
+        (0, 0, 128),
+        (128, 0, 0),
+        (0, 128, 128),
+        (170, 255, 195),
+        (255, 215, 180),
+    ];
+    let entries: Vec<(char, (u8, u8, u8))> = "0123456789abcdef".chars().zip(colors).collect();
+    ColorAlphabet::new(&entries).unwrap()
+}
+
+#[test]
+fn scaled_and_recompressed_images_decode() {
+    let alphabet = hex_palette();
+    // Every symbol next to a few different neighbours
+    let text = "0123456789abcdef".repeat(2) + "fedcba9876543210" + "02468ace13579bdf";
+    let image = create_img_with_alphabet(&text, "bitcoin", &alphabet).unwrap();
+    let damaged = scale_and_compress(&image, 8, 85);
+
+    // Exact matching cannot read it back
+    assert_ne!(
+        colorsquares::decode_image_with_alphabet(&damaged, &alphabet),
+        Some(text.clone())
+    );
+
+    let options = DecodeOptions {
+        symbols: Some(text.len()),
+        ..DecodeOptions::default()
+    };
+    let decoded = decode_image_tolerant(&damaged, &alphabet, &options).unwrap();
+    assert_eq!(decoded.text(), text);
+    assert!(decoded.symbols.iter().any(|symbol| symbol.distance > 1.0));
+    assert!(
+        decoded.min_confidence() > 0.2,
+        "{}",
+        decoded.min_confidence()
+    );
+
+    // A threshold tighter than the damage
+    let strict = DecodeOptions {
+        max_distance: 1.0,
+        ..options
+    };
+    assert!(matches!(
+        decode_image_tolerant(&damaged, &alphabet, &strict),
+        Err(Error::UnreadableSymbol { .. })
+    ));
+}
+
+#[test]
+fn colors_far_from_the_palette_are_rejected() {
+    let alphabet = ColorAlphabet::new(&[('A', (0, 0, 0)), ('B', (255, 255, 255))]).unwrap();
+    let image = create_img_with_alphabet("ABBA", "none", &alphabet).unwrap();
+
+    // Red is far from both black and white
+    let mut img = load(&image).to_rgba8();
+    img.put_pixel(2, 0, Rgba([255, 0, 0, 255]));
+    let tinted = png(&DynamicImage::ImageRgba8(img.clone()));
+    match decode_image_tolerant(&tinted, &alphabet, &DecodeOptions::default()) {
+        // Column 2 holds the second symbol of the text
+        Err(Error::UnreadableSymbol { position, distance }) => {
+            assert_eq!(position, 1);
+            assert!(distance > 10.0);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // Mid grey is as far from black as from white: read, but without
+    // confidence
+    img.put_pixel(2, 0, Rgba([119, 119, 119, 255]));
+    let grey = png(&DynamicImage::ImageRgba8(img));
+    let options = DecodeOptions {
+        max_distance: 100.0,
+        ..DecodeOptions::default()
+    };
+    let decoded = decode_image_tolerant(&grey, &alphabet, &options).unwrap();
+    assert!(decoded.symbols[1].confidence < 0.1);
+    assert_eq!(decoded.symbols[0].confidence, 1.0);
+}
+
+#[test]
+fn symbol_count_must_fit_the_image() {
+    let image = create_img("QUJD", "none").unwrap();
+    for symbols in [0, 5] {
+        let options = DecodeOptions {
+            symbols: Some(symbols),
+            ..DecodeOptions::default()
+        };
+        assert!(matches!(
+            decode_image_tolerant(&image, ColorAlphabet::standard(), &options),
+            Err(Error::InvalidImage)
+        ));
+    }
+}
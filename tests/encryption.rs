@@ -0,0 +1,375 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    create_img, decode_image_and_extract_text, decrypt_bytes, decrypt_image, decrypt_with,
+    decrypt_with_context, decrypt_with_passphrase, decrypt_with_ttl, decrypt_with_ttl_at,
+    encrypt_bytes, encrypt_to_image, encrypt_with, encrypt_with_context, encrypt_with_options,
+    encrypt_with_passphrase, envelope_issued_at, CipherSuite, Compression, EncryptOptions, Error,
+    Kdf, Key,
+};
+use common::{key, key32};
+use std::time::{Duration, SystemTime};
+
+// Cheap parameters so the passphrase tests stay fast
+fn fast_kdf() -> Kdf {
+    Kdf::Pbkdf2Sha256 { rounds: 1_000 }
+}
+
+#[test]
+fn empty_input_round_trips() {
+    let encrypted = encrypt_with("", &key()).unwrap();
+    assert_eq!(decrypt_with(&encrypted, &key()).unwrap().as_str(), "");
+}
+
+#[test]
+fn short_inputs_round_trip() {
+    for input in ["a", "short", "123456789", "0123456789"] {
+        let encrypted = encrypt_with(input, &key()).unwrap();
+        assert_eq!(decrypt_with(&encrypted, &key()).unwrap().as_str(), input);
+    }
+}
+
+#[test]
+fn multibyte_character_on_tenth_byte_round_trips() {
+    // 'é' is two bytes and straddles the byte 10 boundary
+    let input = "123456789é and more";
+    let encrypted = encrypt_with(input, &key()).unwrap();
+    assert_eq!(decrypt_with(&encrypted, &key()).unwrap().as_str(), input);
+}
+
+#[test]
+fn binary_input_round_trips() {
+    let input = [0xff, 0xfe, 0x00, 0x80, b'a'];
+    let encrypted = encrypt_bytes(&input, &key()).unwrap();
+    assert_eq!(decrypt_bytes(&encrypted, &key()).unwrap().as_bytes(), input);
+}
+
+#[test]
+fn non_utf8_plaintext_is_an_error_not_replaced() {
+    let encrypted = STANDARD.encode(encrypt_bytes(&[0xc3, 0x28], &key()).unwrap());
+    assert!(matches!(
+        decrypt_with(&encrypted, &key()),
+        Err(Error::InvalidUtf8)
+    ));
+}
+
+#[test]
+fn compressed_and_padded_inputs_round_trip() {
+    let long = "colorsquares ".repeat(50);
+    for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+        for pad in [false, true] {
+            let options = EncryptOptions {
+                compression,
+                pad,
+                ..EncryptOptions::default()
+            };
+            for input in ["", "a", long.as_str()] {
+                let encrypted = encrypt_with_options(input, &key(), &options).unwrap();
+                assert_eq!(decrypt_with(&encrypted, &key()).unwrap().as_str(), input);
+            }
+        }
+    }
+}
+
+#[test]
+fn compression_shrinks_repetitive_input() {
+    let input = "colorsquares ".repeat(50);
+    let options = EncryptOptions {
+        compression: Compression::Deflate,
+        ..EncryptOptions::default()
+    };
+    let compressed = encrypt_with_options(&input, &key(), &options).unwrap();
+    assert!(compressed.len() < encrypt_with(&input, &key()).unwrap().len());
+}
+
+#[test]
+fn same_input_encrypts_to_different_envelopes() {
+    let input = "the same secret every time";
+    let first = encrypt_with(input, &key()).unwrap();
+    let second = encrypt_with(input, &key()).unwrap();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn envelope_does_not_contain_plaintext_prefix() {
+    let input = "SECRETPREFIX followed by the rest of the message";
+    let encrypted = encrypt_with(input, &key()).unwrap();
+    let envelope = STANDARD.decode(encrypted).unwrap();
+
+    let prefix = STANDARD.encode(&input[..10]);
+    assert!(!envelope
+        .windows(prefix.len())
+        .any(|window| window == prefix.as_bytes()));
+    assert!(!envelope
+        .windows(10)
+        .any(|window| window == &input.as_bytes()[..10]));
+}
+
+#[test]
+fn short_key_is_rejected() {
+    let short = Key::new(b"welovenfts");
+    assert!(matches!(
+        encrypt_with("some input", &short),
+        Err(Error::InvalidKeyLength {
+            expected: 16,
+            actual: 10
+        })
+    ));
+}
+
+#[test]
+fn keys_must_have_the_suite_length() {
+    for len in [0, 15, 17, 24, 32] {
+        let key = Key::new(&vec![7; len]);
+        assert!(matches!(
+            encrypt_with("some input", &key),
+            Err(Error::InvalidKeyLength { expected: 16, actual }) if actual == len
+        ));
+    }
+
+    let encrypted = encrypt_with("some input", &key()).unwrap();
+    assert!(matches!(
+        decrypt_with(&encrypted, &Key::new(&[7; 32])),
+        Err(Error::InvalidKeyLength {
+            expected: 16,
+            actual: 32
+        })
+    ));
+
+    // 32 byte keys are for the 256-bit suites
+    let options = EncryptOptions {
+        suite: CipherSuite::Aes256Gcm,
+        ..EncryptOptions::default()
+    };
+    let long_key = key32();
+    let encrypted = encrypt_with_options("some input", &long_key, &options).unwrap();
+    assert_eq!(
+        decrypt_with(&encrypted, &long_key).unwrap().as_str(),
+        "some input"
+    );
+    assert!(matches!(
+        encrypt_with_options("some input", &key(), &options),
+        Err(Error::InvalidKeyLength {
+            expected: 32,
+            actual: 16
+        })
+    ));
+}
+
+#[test]
+fn wrong_key_fails_to_decrypt() {
+    let encrypted = encrypt_with("some input", &key()).unwrap();
+    let other = Key::new(b"fedcba9876543210");
+    assert!(matches!(
+        decrypt_with(&encrypted, &other),
+        Err(Error::AuthenticationFailed)
+    ));
+}
+
+#[test]
+fn every_flipped_byte_is_detected() {
+    let suites = [
+        (CipherSuite::Aes128CbcHmacSha256, Key::new(&[7; 16])),
+        (CipherSuite::Aes128Gcm, Key::new(&[7; 16])),
+        (CipherSuite::Aes256Gcm, Key::new(&[7; 32])),
+        (CipherSuite::ChaCha20Poly1305, Key::new(&[7; 32])),
+    ];
+    for (suite, key) in suites {
+        let options = EncryptOptions {
+            suite,
+            ..Default::default()
+        };
+        let envelope = STANDARD
+            .decode(encrypt_with_options("some input", &key, &options).unwrap())
+            .unwrap();
+        for i in 0..envelope.len() {
+            let mut tampered = envelope.clone();
+            tampered[i] ^= 0x01;
+            let result = decrypt_with(&STANDARD.encode(&tampered), &key);
+            // The magic, version, suite, MAC and key source bytes select how
+            // the rest is read, and a flip there is reported as such
+            let identifies_format = i < 7
+                && matches!(
+                    result,
+                    Err(Error::UnsupportedVersion(_))
+                        | Err(Error::UnknownAlgorithm(_))
+                        | Err(Error::InvalidKeyLength { .. })
+                        | Err(Error::InvalidKdfParameters)
+                );
+            assert!(
+                identifies_format
+                    || matches!(
+                        result,
+                        Err(Error::AuthenticationFailed) | Err(Error::Malformed)
+                    ),
+                "{:?}: flipping byte {} gave {:?}",
+                suite,
+                i,
+                result.map(|_| ())
+            );
+        }
+    }
+}
+
+#[test]
+fn truncated_envelope_is_rejected() {
+    let truncated = STANDARD.encode([0u8; 20]);
+    assert!(decrypt_with(&truncated, &key()).is_err());
+}
+
+#[test]
+fn passphrase_round_trips_short_and_empty_inputs() {
+    for input in ["", "x", "123456789é"] {
+        let encrypted = encrypt_with_passphrase(input, "correct horse", &fast_kdf()).unwrap();
+        assert_eq!(
+            decrypt_with_passphrase(&encrypted, "correct horse")
+                .unwrap()
+                .as_str(),
+            input
+        );
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn legacy_shims_handle_short_inputs() {
+    for input in ["", "hi", "123456789é"] {
+        let encrypted = colorsquares::encrypts(input);
+        assert_eq!(colorsquares::decrypts(&encrypted).as_deref(), Some(input));
+    }
+}
+
+#[test]
+fn keys_and_plaintext_are_redacted_in_debug() {
+    let encrypted = encrypt_with("top secret", &key()).unwrap();
+    let decrypted = decrypt_with(&encrypted, &key()).unwrap();
+    let decrypted_bytes = decrypt_bytes(&STANDARD.decode(&encrypted).unwrap(), &key()).unwrap();
+
+    for debug in [
+        format!("{:?}", key()),
+        format!("{:?}", decrypted),
+        format!("{:?}", decrypted_bytes),
+    ] {
+        assert!(debug.contains("REDACTED"));
+        assert!(!debug.contains("top secret"));
+        assert!(!debug.contains("0123"));
+    }
+}
+
+#[test]
+fn ttl_accepts_fresh_and_rejects_old_or_future_envelopes() {
+    let encrypted = encrypt_with("access", &key()).unwrap();
+    let issued_at = envelope_issued_at(&encrypted).unwrap().unwrap();
+    let hour = Duration::from_secs(3600);
+    let skew = Duration::from_secs(60);
+
+    let decrypted = decrypt_with_ttl(&encrypted, &key(), hour, skew).unwrap();
+    assert_eq!(decrypted.as_str(), "access");
+
+    let at = |offset: i64| {
+        let now = if offset < 0 {
+            issued_at - Duration::from_secs(offset.unsigned_abs())
+        } else {
+            issued_at + Duration::from_secs(offset as u64)
+        };
+        decrypt_with_ttl_at(&encrypted, &key(), hour, skew, now)
+    };
+    assert!(at(3600 + 60).is_ok());
+    assert!(matches!(at(3600 + 61), Err(Error::Expired)));
+    assert!(at(-60).is_ok());
+    assert!(matches!(at(-61), Err(Error::Expired)));
+}
+
+#[test]
+fn ttl_timestamp_is_authenticated() {
+    let mut data = STANDARD
+        .decode(encrypt_with("access", &key()).unwrap())
+        .unwrap();
+    // Low byte of the issued at time, which follows the 8 byte fixed header
+    data[15] ^= 1;
+    assert!(matches!(
+        decrypt_with_ttl_at(
+            &STANDARD.encode(&data),
+            &key(),
+            Duration::from_secs(3600),
+            Duration::ZERO,
+            SystemTime::now(),
+        ),
+        Err(Error::AuthenticationFailed)
+    ));
+}
+
+#[test]
+fn out_of_range_timestamp_is_malformed() {
+    let mut data = STANDARD
+        .decode(encrypt_with("access", &key()).unwrap())
+        .unwrap();
+    data[8..16].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(matches!(
+        envelope_issued_at(&STANDARD.encode(&data)),
+        Err(Error::Malformed)
+    ));
+}
+
+#[test]
+fn context_must_match_to_decrypt() {
+    let context = ["issuer:acme", "purpose:door"];
+    let encrypted = encrypt_with_context("open", &key(), &context).unwrap();
+    assert_eq!(
+        decrypt_with_context(&encrypted, &key(), &context)
+            .unwrap()
+            .as_str(),
+        "open"
+    );
+
+    for wrong in [
+        &[][..],
+        &["issuer:acme"][..],
+        &["purpose:door", "issuer:acme"][..],
+        &["issuer:acmepurpose:door"][..],
+        &["issuer:acme", "purpose:doo", "r"][..],
+    ] {
+        assert!(matches!(
+            decrypt_with_context(&encrypted, &key(), wrong),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+    assert!(matches!(
+        decrypt_with(&encrypted, &key()),
+        Err(Error::AuthenticationFailed)
+    ));
+
+    // No context is the same as an empty one
+    let encrypted = encrypt_with("open", &key()).unwrap();
+    assert_eq!(
+        decrypt_with_context(&encrypted, &key(), &[])
+            .unwrap()
+            .as_str(),
+        "open"
+    );
+}
+
+#[test]
+fn image_is_bound_to_its_watermark() {
+    let image = encrypt_to_image("open", &key(), "bitcoin", &["recipient:bob"]).unwrap();
+    assert_eq!(
+        decrypt_image(&image, &key(), "bitcoin", &["recipient:bob"])
+            .unwrap()
+            .as_str(),
+        "open"
+    );
+    assert!(matches!(
+        decrypt_image(&image, &key(), "bitcoin", &["recipient:eve"]),
+        Err(Error::AuthenticationFailed)
+    ));
+
+    // Re-rendered under another watermark
+    let extracted = decode_image_and_extract_text(&image).unwrap();
+    let rerendered = create_img(&extracted.replace('*', "+"), "ethereum").unwrap();
+    assert!(matches!(
+        decrypt_image(&rerendered, &key(), "ethereum", &["recipient:bob"]),
+        Err(Error::AuthenticationFailed)
+    ));
+}
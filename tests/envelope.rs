@@ -0,0 +1,99 @@
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    decrypt_with, decrypt_with_passphrase, encrypt_with, encrypt_with_passphrase,
+    envelope_issued_at, Error, Kdf,
+};
+use common::key;
+
+// Offsets into the fixed start of a versioned envelope
+const VERSION: usize = 3;
+const SUITE: usize = 4;
+const KEY_SOURCE: usize = 6;
+const FLAGS: usize = 7;
+
+fn envelope() -> Vec<u8> {
+    STANDARD
+        .decode(encrypt_with("some input", &key()).unwrap())
+        .unwrap()
+}
+
+fn passphrase_envelope() -> Vec<u8> {
+    let kdf = Kdf::Pbkdf2Sha256 { rounds: 1_000 };
+    STANDARD
+        .decode(encrypt_with_passphrase("some input", "passphrase", &kdf).unwrap())
+        .unwrap()
+}
+
+fn with_byte(data: &[u8], at: usize, value: u8) -> String {
+    let mut data = data.to_vec();
+    data[at] = value;
+    STANDARD.encode(data)
+}
+
+#[test]
+fn every_truncation_is_malformed() {
+    for data in [envelope(), passphrase_envelope()] {
+        for len in 0..data.len() {
+            let truncated = STANDARD.encode(&data[..len]);
+            assert!(
+                matches!(envelope_issued_at(&truncated), Err(Error::Malformed)),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+}
+
+#[test]
+fn unknown_version_is_unsupported() {
+    let data = envelope();
+    assert!(matches!(
+        decrypt_with(&with_byte(&data, VERSION, 2), &key()),
+        Err(Error::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        envelope_issued_at(&with_byte(&data, VERSION, 0)),
+        Err(Error::UnsupportedVersion(0))
+    ));
+}
+
+#[test]
+fn unknown_suite_is_rejected() {
+    let data = envelope();
+    for id in [0, 5, 0xff] {
+        assert!(matches!(
+            decrypt_with(&with_byte(&data, SUITE, id), &key()),
+            Err(Error::UnknownAlgorithm(unknown)) if unknown == id
+        ));
+    }
+}
+
+#[test]
+fn unknown_kdf_is_rejected() {
+    let data = passphrase_envelope();
+    for id in [3, 7] {
+        assert!(matches!(
+            decrypt_with_passphrase(&with_byte(&data, KEY_SOURCE, id), "passphrase"),
+            Err(Error::UnknownAlgorithm(unknown)) if unknown == id
+        ));
+    }
+}
+
+#[test]
+fn reserved_flags_are_rejected() {
+    let data = envelope();
+    for bit in [0x20, 0x40, 0x80] {
+        let flagged = with_byte(&data, FLAGS, data[FLAGS] | bit);
+        assert!(matches!(
+            decrypt_with(&flagged, &key()),
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            envelope_issued_at(&flagged),
+            Err(Error::Malformed)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+// Test vectors from the Fernet spec, https://github.com/fernet/spec
+use colorsquares::{
+    create_fernet_img, decode_fernet_key, decrypt_fernet, decrypt_fernet_at, encode_fernet_key,
+    encrypt_fernet, encrypt_fernet_with_iv, extract_fernet_token, Error, Key,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECRET: &str = "cw_0x689RpI-jtRR7oE8h_eQsKImvJapLeSbXpwF4e4=";
+
+// 1985-10-26T01:20:00-07:00
+const NOW: u64 = 499_162_800;
+
+const TTL: Option<Duration> = Some(Duration::from_secs(60));
+
+fn key() -> Key {
+    decode_fernet_key(SECRET).unwrap()
+}
+
+// Which error an invalid token must fail with
+type ErrorCheck = fn(&Error) -> bool;
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn generate_vector() {
+    let iv: [u8; 16] = std::array::from_fn(|i| i as u8);
+    let token = encrypt_fernet_with_iv(b"hello", &key(), at(NOW), &iv).unwrap();
+    assert_eq!(
+        token,
+        "gAAAAAAdwJ6wAAECAwQFBgcICQoLDA0ODy021cpGVWKZ_eEwCGM4BLLF_5CV9dOPmrhuVUPgJobwOz7JcbmrR64jVmpU4IwqDA=="
+    );
+}
+
+#[test]
+fn verify_vector() {
+    let token = "gAAAAAAdwJ6wAAECAwQFBgcICQoLDA0ODy021cpGVWKZ_eEwCGM4BLLF_5CV9dOPmrhuVUPgJobwOz7JcbmrR64jVmpU4IwqDA==";
+    let decrypted = decrypt_fernet_at(token, &key(), TTL, at(NOW + 1)).unwrap();
+    assert_eq!(decrypted.as_bytes(), b"hello");
+}
+
+#[test]
+fn invalid_vectors() {
+    let cases: [(&str, &str, u64, ErrorCheck); 8] = [
+        (
+            "incorrect mac",
+            "gAAAAAAdwJ6xAAECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPAl1-szkFVzXTuGb4hR8AKtwcaX1YdykQUFBQUFBQUFBQQ==",
+            NOW + 1,
+            |err| matches!(err, Error::AuthenticationFailed),
+        ),
+        (
+            "too short",
+            "gAAAAAAdwJ6xAAECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPA==",
+            NOW + 1,
+            |err| matches!(err, Error::Malformed),
+        ),
+        (
+            "invalid base64",
+            "%%%%%%%%%%%%%AECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPAl1-szkFVzXTuGb4hR8AKtwcaX1YdykRtfsH-p1YsUD2Q==",
+            NOW + 1,
+            |err| matches!(err, Error::InvalidEncoding),
+        ),
+        (
+            "payload size not multiple of block size",
+            "gAAAAAAdwJ6xAAECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPOm73QeoCk9uGib28Xe5vz6oxq5nmxbx_v7mrfyudzUm",
+            NOW + 1,
+            |err| matches!(err, Error::Malformed),
+        ),
+        (
+            "payload padding error",
+            "gAAAAAAdwJ6xAAECAwQFBgcICQoLDA0ODz4LEpdELGQAad7aNEHbf-JkLPIpuiYRLQ3RtXatOYREu2FWke6CnJNYIbkuKNqOhw==",
+            NOW + 1,
+            |err| matches!(err, Error::Decryption),
+        ),
+        (
+            "far-future TS (unacceptable clock skew)",
+            "gAAAAAAdwStRAAECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPAnja1xKYyhd-Y6mSkTOyTGJmw2Xc2a6kBd-iX9b_qXQcw==",
+            NOW + 1,
+            |err| matches!(err, Error::Expired),
+        ),
+        (
+            "expired TTL",
+            "gAAAAAAdwJ6xAAECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPAl1-szkFVzXTuGb4hR8AKtwcaX1YdykRtfsH-p1YsUD2Q==",
+            NOW + 91,
+            |err| matches!(err, Error::Expired),
+        ),
+        (
+            "incorrect IV (causes padding error)",
+            "gAAAAAAdwJ6xBQECAwQFBgcICQoLDA0OD3HkMATM5lFqGaerZ-fWPAkLhFLHpGtDBRLRTZeUfWgHSv49TF2AUEZ1TIvcZjK1zQ==",
+            NOW + 1,
+            |err| matches!(err, Error::Decryption),
+        ),
+    ];
+
+    for (desc, token, now, expected) in cases {
+        match decrypt_fernet_at(token, &key(), TTL, at(now)) {
+            Err(err) => assert!(expected(&err), "{}: unexpected {:?}", desc, err),
+            Ok(_) => panic!("{}: decrypted", desc),
+        }
+    }
+}
+
+#[test]
+fn round_trips_through_an_image() {
+    let key = Key::generate(32).unwrap();
+    let encoded_key = encode_fernet_key(&key).unwrap();
+    assert_eq!(
+        decode_fernet_key(&encoded_key).unwrap().as_bytes(),
+        key.as_bytes()
+    );
+
+    // Enough inputs that the token contains '-' and '_'
+    for input in ["", "hello", "a longer message to render as colorsquares"] {
+        for _ in 0..8 {
+            let token = encrypt_fernet(input.as_bytes(), &key).unwrap();
+            let image = create_fernet_img(&token, "bitcoin").unwrap();
+            let extracted = extract_fernet_token(&image).unwrap();
+            assert_eq!(extracted, token);
+            let decrypted = decrypt_fernet(&extracted, &key, TTL).unwrap();
+            assert_eq!(decrypted.as_bytes(), input.as_bytes());
+        }
+    }
+}
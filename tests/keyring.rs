@@ -0,0 +1,174 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    decrypt_with_provider, encrypt_with_provider, encrypt_with_provider_and_options,
+    envelope_issued_at, envelope_key_id, reencrypt, Compression, EncryptOptions, EnvKeyring, Error,
+    FileKeyring, InMemoryKeyring, Key, KeyProvider,
+};
+use std::env;
+
+const OLD: &[u8; 16] = b"0123456789abcdef";
+const NEW: &[u8; 16] = b"fedcba9876543210";
+
+// Byte offset of the flags in the envelope header
+const FLAGS: usize = 7;
+
+fn keyring_file() -> String {
+    format!(
+        "# rotated yearly\n\n2025 {}\n  \n# current\n2026 {}\n",
+        STANDARD.encode(OLD),
+        STANDARD.encode(NEW)
+    )
+}
+
+#[test]
+fn retired_key_decrypts_after_rotation() {
+    let mut keyring = InMemoryKeyring::new("2025", Key::new(OLD)).unwrap();
+    let old = encrypt_with_provider("some input", &keyring).unwrap();
+
+    keyring.add("2026", Key::new(NEW)).unwrap();
+    keyring.set_current("2026").unwrap();
+    let new = encrypt_with_provider("some input", &keyring).unwrap();
+
+    assert_eq!(envelope_key_id(&old).unwrap(), "2025");
+    assert_eq!(envelope_key_id(&new).unwrap(), "2026");
+    for encrypted in [old, new] {
+        assert_eq!(
+            decrypt_with_provider(&encrypted, &keyring)
+                .unwrap()
+                .as_str(),
+            "some input"
+        );
+    }
+}
+
+#[test]
+fn file_keyring_uses_its_last_key() {
+    let keyring = FileKeyring::parse(&keyring_file()).unwrap();
+    assert_eq!(keyring.current_key_id().unwrap(), "2026");
+    assert_eq!(keyring.key("2025").unwrap().as_bytes(), OLD);
+    assert_eq!(keyring.key("2026").unwrap().as_bytes(), NEW);
+
+    let encrypted = encrypt_with_provider("some input", &keyring).unwrap();
+    assert_eq!(envelope_key_id(&encrypted).unwrap(), "2026");
+}
+
+#[test]
+fn malformed_keyring_files_are_rejected() {
+    let key = STANDARD.encode(OLD);
+    for contents in [
+        String::new(),
+        "# only comments\n\n".to_string(),
+        "2025\n".to_string(),
+        format!("2025 {} extra\n", key),
+        "2025 not*base64\n".to_string(),
+        format!("2025 {}\n2026\n", key),
+    ] {
+        assert!(
+            matches!(FileKeyring::parse(&contents), Err(Error::InvalidKeyring)),
+            "{:?}",
+            contents
+        );
+    }
+}
+
+#[test]
+fn env_keyring_reads_keys_when_needed() {
+    // Variable names unique to this test, as tests share the environment
+    env::set_var("KEYRING_TEST_CURRENT_KEY_ID", "2025");
+    env::set_var("KEYRING_TEST_KEY_2025", STANDARD.encode(OLD));
+    let keyring = EnvKeyring::new("KEYRING_TEST");
+    let old = encrypt_with_provider("some input", &keyring).unwrap();
+
+    env::set_var("KEYRING_TEST_KEY_2026", STANDARD.encode(NEW));
+    env::set_var("KEYRING_TEST_CURRENT_KEY_ID", "2026");
+    let new = encrypt_with_provider("some input", &keyring).unwrap();
+    assert_eq!(envelope_key_id(&new).unwrap(), "2026");
+    for encrypted in [old, new] {
+        assert_eq!(
+            decrypt_with_provider(&encrypted, &keyring)
+                .unwrap()
+                .as_str(),
+            "some input"
+        );
+    }
+
+    assert!(matches!(
+        EnvKeyring::new("KEYRING_TEST_UNSET").current_key_id(),
+        Err(Error::InvalidKeyring)
+    ));
+}
+
+#[test]
+fn unknown_key_id_is_reported() {
+    let keyring = InMemoryKeyring::new("2025", Key::new(OLD)).unwrap();
+    let encrypted = encrypt_with_provider("some input", &keyring).unwrap();
+    let other = InMemoryKeyring::new("2026", Key::new(NEW)).unwrap();
+    assert!(matches!(
+        decrypt_with_provider(&encrypted, &other),
+        Err(Error::UnknownKeyId(key_id)) if key_id == "2025"
+    ));
+
+    let mut keyring = keyring;
+    assert!(matches!(
+        keyring.set_current("2026"),
+        Err(Error::UnknownKeyId(_))
+    ));
+}
+
+#[test]
+fn reencrypt_moves_to_the_current_key() {
+    let mut keyring = InMemoryKeyring::new("2025", Key::new(OLD)).unwrap();
+    let old = encrypt_with_provider("some input", &keyring).unwrap();
+    keyring.add("2026", Key::new(NEW)).unwrap();
+    keyring.set_current("2026").unwrap();
+
+    let moved = reencrypt(&old, &keyring).unwrap();
+    assert_eq!(envelope_key_id(&moved).unwrap(), "2026");
+    assert_eq!(
+        envelope_issued_at(&moved).unwrap(),
+        envelope_issued_at(&old).unwrap()
+    );
+    assert!(envelope_issued_at(&moved).unwrap().is_some());
+
+    // Once the old key is gone, only the moved envelope opens
+    let current = InMemoryKeyring::new("2026", Key::new(NEW)).unwrap();
+    assert_eq!(
+        decrypt_with_provider(&moved, &current).unwrap().as_str(),
+        "some input"
+    );
+    assert!(decrypt_with_provider(&old, &current).is_err());
+}
+
+#[test]
+fn reencrypt_keeps_compression_and_padding() {
+    let mut keyring = InMemoryKeyring::new("2025", Key::new(OLD)).unwrap();
+    keyring.add("2026", Key::new(NEW)).unwrap();
+    let input = "some input ".repeat(50);
+    for (compression, pad) in [
+        (Compression::None, true),
+        (Compression::Deflate, false),
+        (Compression::Zstd, true),
+    ] {
+        let options = EncryptOptions {
+            compression,
+            pad,
+            ..EncryptOptions::default()
+        };
+        keyring.set_current("2025").unwrap();
+        let old = encrypt_with_provider_and_options(&input, &keyring, &options).unwrap();
+        keyring.set_current("2026").unwrap();
+
+        let moved = reencrypt(&old, &keyring).unwrap();
+        let old = STANDARD.decode(old).unwrap();
+        let moved = STANDARD.decode(moved).unwrap();
+        assert_eq!(moved[FLAGS], old[FLAGS], "{:?}, pad {}", compression, pad);
+        assert_eq!(moved.len(), old.len());
+        assert_eq!(
+            decrypt_with_provider(&STANDARD.encode(&moved), &keyring)
+                .unwrap()
+                .as_str(),
+            input
+        );
+    }
+}
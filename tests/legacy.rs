@@ -0,0 +1,148 @@
+// Output of the original hardcoded-key `encrypts`. All of them contain '+',
+// which older versions read back from an image as '*'.
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    create_img, create_img_with_alphabet, decrypt_legacy, decrypt_legacy_image, decrypt_with,
+    encrypt_with, envelope_issued_at, migrate_legacy, migrate_legacy_image, ColorAlphabet, Error,
+    Key,
+};
+use common::key;
+
+const LEGACY: [(&str, &str); 3] = [
+    (
+        "DataYouWantToEncrypt",
+        "UkdGMFlWbHZkVmRoYmc9PVUbDsTrdR+6cm3HaaBHnsNrjnFaQ0AOh9WpLbiK9TVvcCnI3xI9zh2N+y5qiW2jHCQRVL3B5O0Mmyrbmp/kRQs=",
+    ),
+    (
+        "membership #1042 gold",
+        "YldWdFltVnljMmhwY0E9PeCKGRSgzUYGYba4O6xXdPzG4c1HYHpjIxHQrH6sfXTv+k6IXDwRLroI2MRmJFKs2ajT94i8l60BlOvUQN0jSFU=",
+    ),
+    (
+        "access granted to vault 7",
+        "WVdOalpYTnpJR2R5WVE9PeHGmbKO1DKU0ZZnRJCsf2vZC70W4iYapVYCRArM5k0dnqJBpRRSwMCFW8R3e4xFYtJTts13/+o+CydY8Akl6xI=",
+    ),
+];
+
+#[test]
+fn decrypts_legacy_payloads_and_images() {
+    for (plaintext, encoded) in LEGACY {
+        assert_eq!(decrypt_legacy(encoded).unwrap().as_str(), plaintext);
+
+        let image = create_img(encoded, "bitcoin").unwrap();
+        assert_eq!(decrypt_legacy_image(&image).unwrap().as_str(), plaintext);
+    }
+}
+
+#[test]
+fn legacy_image_drawn_from_text_with_star_decrypts() {
+    // An alphabet that draws '*' in the color of '+', as older versions read it
+    let entries: Vec<(char, (u8, u8, u8))> = ColorAlphabet::standard()
+        .entries()
+        .iter()
+        .map(|&(symbol, color)| (if symbol == '+' { '*' } else { symbol }, color))
+        .collect();
+    let alphabet = ColorAlphabet::new(&entries).unwrap();
+
+    for (plaintext, encoded) in LEGACY {
+        let text = encoded.replace('+', "*");
+        let image = create_img_with_alphabet(&text, "bitcoin", &alphabet).unwrap();
+        assert_eq!(decrypt_legacy_image(&image).unwrap().as_str(), plaintext);
+    }
+}
+
+#[test]
+fn migrates_to_the_versioned_format() {
+    for (plaintext, encoded) in LEGACY {
+        let migrated = migrate_legacy(encoded, &key()).unwrap();
+        assert_eq!(decrypt_with(&migrated, &key()).unwrap().as_str(), plaintext);
+
+        let image = create_img(encoded, "ethereum").unwrap();
+        let migrated_image = migrate_legacy_image(&image, &key(), "ethereum").unwrap();
+        let migrated = colorsquares::decode_image_and_extract_text(&migrated_image).unwrap();
+        assert_eq!(decrypt_with(&migrated, &key()).unwrap().as_str(), plaintext);
+    }
+}
+
+#[test]
+fn rejects_other_layouts() {
+    let versioned = encrypt_with("DataYouWantToEncrypt", &key()).unwrap();
+    assert!(matches!(decrypt_legacy(&versioned), Err(Error::Malformed)));
+    assert!(matches!(decrypt_legacy("c2hvcnQ="), Err(Error::Malformed)));
+
+    // A tampered HMAC
+    let (_, encoded) = LEGACY[0];
+    let tampered = encoded.replacen('N', "M", 1);
+    assert!(matches!(
+        decrypt_legacy(&tampered),
+        Err(Error::AuthenticationFailed)
+    ));
+}
+
+// The key of the original `encrypts`, as `Key::legacy` pads it
+fn legacy_key() -> Key {
+    Key::new(b"welovenfts\0\0\0\0\0\0")
+}
+
+// Byte offset of the payload in a default CBC envelope
+const PAYLOAD: usize = 20;
+const IV_LEN: usize = 16;
+
+#[test]
+fn flipped_iv_or_header_fails_authentication() {
+    let envelope = STANDARD
+        .decode(encrypt_with("DataYouWantToEncrypt", &key()).unwrap())
+        .unwrap();
+    for i in PAYLOAD..PAYLOAD + IV_LEN {
+        let mut tampered = envelope.clone();
+        tampered[i] ^= 0x01;
+        assert!(matches!(
+            decrypt_with(&STANDARD.encode(&tampered), &key()),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+    // Every change to the header that still parses is caught by the HMAC
+    for i in 0..PAYLOAD {
+        for bit in 0..8 {
+            let mut tampered = envelope.clone();
+            tampered[i] ^= 1 << bit;
+            let tampered = STANDARD.encode(&tampered);
+            if envelope_issued_at(&tampered).is_ok() {
+                assert!(
+                    matches!(
+                        decrypt_with(&tampered, &key()),
+                        Err(Error::AuthenticationFailed)
+                    ),
+                    "flipping bit {} of byte {}",
+                    bit,
+                    i
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn old_layout_opens_only_as_legacy() {
+    let (plaintext, encoded) = LEGACY[0];
+    let old = STANDARD.decode(encoded).unwrap();
+    assert_eq!(decrypt_legacy(encoded).unwrap().as_str(), plaintext);
+    assert!(matches!(
+        decrypt_with(encoded, &legacy_key()),
+        Err(Error::Malformed)
+    ));
+
+    // The same bytes as the payload of a versioned CBC envelope
+    let mut versioned = STANDARD
+        .decode(encrypt_with(plaintext, &legacy_key()).unwrap())
+        .unwrap();
+    versioned.truncate(PAYLOAD - 4);
+    versioned.extend_from_slice(&(old.len() as u32).to_be_bytes());
+    versioned.extend_from_slice(&old);
+    assert!(matches!(
+        decrypt_with(&STANDARD.encode(&versioned), &legacy_key()),
+        Err(Error::AuthenticationFailed)
+    ));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use colorsquares::{
+    create_img_with_alphabet, decode_image_tolerant, encrypt_with, generate_palette, ColorAlphabet,
+    DecodeOptions, Error, PaletteConstraints,
+};
+use common::key;
+
+const BASE64: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+// CIELAB lightness of an sRGB color
+fn lightness((r, g, b): (u8, u8, u8)) -> f64 {
+    let linear = |channel: u8| {
+        let c = channel as f64 / 255.0;
+        if c <= 0.040_45 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let y = 0.212_672_9 * linear(r) + 0.715_152_2 * linear(g) + 0.072_175_0 * linear(b);
+    if y > (6.0f64 / 29.0).powi(3) {
+        116.0 * y.cbrt() - 16.0
+    } else {
+        y * (29.0f64 / 3.0).powi(3)
+    }
+}
+
+#[test]
+fn generated_palettes_are_better_separated_than_the_standard_one() {
+    let (_, _, standard) = ColorAlphabet::standard().closest_pair().unwrap();
+
+    for constraints in [
+        PaletteConstraints::default(),
+        PaletteConstraints::pastel(),
+        PaletteConstraints {
+            print_gamut: true,
+            ..PaletteConstraints::default()
+        },
+    ] {
+        let palette = generate_palette(BASE64, &constraints).unwrap();
+        assert_eq!(palette.alphabet.symbols().collect::<String>(), BASE64);
+        assert!(palette.min_distance > 5.0 * standard, "{:?}", constraints);
+        assert!(
+            palette.min_distance >= ColorAlphabet::DEFAULT_MIN_DISTANCE,
+            "{:?}",
+            constraints
+        );
+
+        // The report is the palette's worst pair
+        let (first, second, distance) = palette.alphabet.closest_pair().unwrap();
+        assert_eq!(palette.closest_pair, (first, second));
+        assert_eq!(palette.min_distance, distance);
+        assert!(
+            ColorAlphabet::with_min_distance(palette.alphabet.entries(), palette.min_distance)
+                .is_ok()
+        );
+    }
+}
+
+#[test]
+fn constraints_are_respected() {
+    let constraints = PaletteConstraints {
+        min_lightness: 40.0,
+        max_lightness: 60.0,
+        ..PaletteConstraints::default()
+    };
+    let palette = generate_palette("0123456789abcdef", &constraints).unwrap();
+    for &(_, color) in palette.alphabet.entries() {
+        let l = lightness(color);
+        assert!((40.0..=60.0).contains(&l), "{:?} {}", color, l);
+    }
+
+    for &(_, color) in generate_palette(BASE64, &PaletteConstraints::pastel())
+        .unwrap()
+        .alphabet
+        .entries()
+    {
+        assert!(lightness(color) >= 70.0, "{:?}", color);
+    }
+
+    // The same palette every time
+    assert_eq!(
+        generate_palette("0123456789abcdef", &constraints)
+            .unwrap()
+            .alphabet
+            .entries(),
+        palette.alphabet.entries()
+    );
+}
+
+#[test]
+fn impossible_requests_are_rejected() {
+    let empty = PaletteConstraints {
+        min_lightness: 60.0,
+        max_lightness: 40.0,
+        ..PaletteConstraints::default()
+    };
+    assert!(matches!(
+        generate_palette("ab", &empty),
+        Err(Error::InvalidAlphabet)
+    ));
+    assert!(matches!(
+        generate_palette("a", &PaletteConstraints::default()),
+        Err(Error::InvalidAlphabet)
+    ));
+    assert!(matches!(
+        generate_palette("aba", &PaletteConstraints::default()),
+        Err(Error::InvalidAlphabet)
+    ));
+}
+
+#[test]
+fn generated_palettes_render_and_load() {
+    let palette = generate_palette(BASE64, &PaletteConstraints::pastel()).unwrap();
+
+    let loaded = ColorAlphabet::from_toml(&palette.to_toml()).unwrap();
+    let mut expected = palette.alphabet.entries().to_vec();
+    let mut actual = loaded.entries().to_vec();
+    expected.sort();
+    actual.sort();
+    assert_eq!(actual, expected);
+
+    let encrypted = encrypt_with("DataYouWantToEncrypt", &key()).unwrap();
+    let image = create_img_with_alphabet(&encrypted, "bitcoin", &loaded).unwrap();
+    let decoded = decode_image_tolerant(&image, &loaded, &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.text(), encrypted);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use colorsquares::{decrypt_with, decrypt_with_passphrase, encrypt_with_passphrase, Error, Kdf};
+use common::key;
+
+// Argon2id with the smallest parameters it accepts, so the tests stay fast
+const FAST_ARGON2: Kdf = Kdf::Argon2id {
+    m_cost: 8,
+    t_cost: 1,
+    p_cost: 1,
+};
+
+const FAST_PBKDF2: Kdf = Kdf::Pbkdf2Sha256 { rounds: 1_000 };
+
+#[test]
+fn round_trips_with_either_kdf() {
+    for kdf in [FAST_ARGON2, FAST_PBKDF2] {
+        let encrypted =
+            encrypt_with_passphrase("DataYouWantToEncrypt", "correct horse", &kdf).unwrap();
+        assert_eq!(
+            decrypt_with_passphrase(&encrypted, "correct horse")
+                .unwrap()
+                .as_str(),
+            "DataYouWantToEncrypt"
+        );
+    }
+}
+
+#[test]
+fn wrong_passphrase_fails() {
+    for kdf in [FAST_ARGON2, FAST_PBKDF2] {
+        let encrypted =
+            encrypt_with_passphrase("DataYouWantToEncrypt", "correct horse", &kdf).unwrap();
+        assert!(matches!(
+            decrypt_with_passphrase(&encrypted, "battery staple"),
+            Err(Error::AuthenticationFailed)
+        ));
+        // Nor is it a key envelope
+        assert!(matches!(
+            decrypt_with(&encrypted, &key()),
+            Err(Error::KeyKindMismatch)
+        ));
+    }
+}
+
+#[test]
+fn out_of_bounds_parameters_are_rejected_when_encrypting() {
+    for kdf in [
+        Kdf::Argon2id {
+            m_cost: 8,
+            t_cost: 65,
+            p_cost: 1,
+        },
+        Kdf::Argon2id {
+            m_cost: (1 << 20) + 1,
+            t_cost: 1,
+            p_cost: 1,
+        },
+        Kdf::Argon2id {
+            m_cost: 1024,
+            t_cost: 1,
+            p_cost: 17,
+        },
+        Kdf::Pbkdf2Sha256 { rounds: 10_000_001 },
+        Kdf::Pbkdf2Sha256 { rounds: 0 },
+    ] {
+        assert!(
+            matches!(
+                encrypt_with_passphrase("DataYouWantToEncrypt", "correct horse", &kdf),
+                Err(Error::InvalidKdfParameters)
+            ),
+            "{:?}",
+            kdf
+        );
+    }
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "openssl")]
+
+use colorsquares::{decrypt_with_private_key, encrypt_to, Error, PrivateKey, PublicKey};
+use openssl::rsa::Rsa;
+
+fn round_trips(private_key: &PrivateKey) {
+    let public_key = private_key.public_key().unwrap();
+    for input in ["", "some input"] {
+        let encrypted = encrypt_to(input, &public_key).unwrap();
+        assert_eq!(
+            decrypt_with_private_key(&encrypted, private_key)
+                .unwrap()
+                .as_str(),
+            input
+        );
+    }
+}
+
+#[test]
+fn x25519_round_trips() {
+    round_trips(&PrivateKey::generate_x25519().unwrap());
+}
+
+#[test]
+fn rsa_oaep_round_trips() {
+    round_trips(&PrivateKey::generate_rsa(2048).unwrap());
+}
+
+#[test]
+fn wrong_private_key_fails() {
+    let x25519 = PrivateKey::generate_x25519().unwrap();
+    let rsa = PrivateKey::generate_rsa(2048).unwrap();
+    for (owner, other) in [
+        (&x25519, PrivateKey::generate_x25519().unwrap()),
+        (&rsa, PrivateKey::generate_rsa(2048).unwrap()),
+    ] {
+        let encrypted = encrypt_to("some input", &owner.public_key().unwrap()).unwrap();
+        assert!(matches!(
+            decrypt_with_private_key(&encrypted, &other),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+
+    // A key of the other type does not apply at all
+    let encrypted = encrypt_to("some input", &x25519.public_key().unwrap()).unwrap();
+    assert!(matches!(
+        decrypt_with_private_key(&encrypted, &rsa),
+        Err(Error::KeyKindMismatch)
+    ));
+}
+
+#[test]
+fn keys_round_trip_through_pem_and_der() {
+    for private_key in [
+        PrivateKey::generate_x25519().unwrap(),
+        PrivateKey::generate_rsa(2048).unwrap(),
+    ] {
+        let public_key = private_key.public_key().unwrap();
+        let from_pem = PublicKey::from_pem(&public_key.to_pem().unwrap()).unwrap();
+        let from_der = PublicKey::from_der(&public_key.to_der().unwrap()).unwrap();
+        assert_eq!(from_pem.to_der().unwrap(), public_key.to_der().unwrap());
+        assert_eq!(from_der.key_id().unwrap(), public_key.key_id().unwrap());
+
+        let encrypted = encrypt_to("some input", &from_pem).unwrap();
+        for imported in [
+            PrivateKey::from_pem(&private_key.to_pem().unwrap()).unwrap(),
+            PrivateKey::from_der(&private_key.to_der().unwrap()).unwrap(),
+        ] {
+            assert_eq!(imported.to_der().unwrap(), private_key.to_der().unwrap());
+            assert_eq!(
+                decrypt_with_private_key(&encrypted, &imported)
+                    .unwrap()
+                    .as_str(),
+                "some input"
+            );
+        }
+    }
+}
+
+#[test]
+fn rsa_under_2048_bits_is_rejected() {
+    assert!(matches!(
+        PrivateKey::generate_rsa(1024),
+        Err(Error::UnsupportedKeyType)
+    ));
+
+    let rsa = Rsa::generate(1024).unwrap();
+    assert!(matches!(
+        PrivateKey::from_pem(&rsa.private_key_to_pem().unwrap()),
+        Err(Error::UnsupportedKeyType)
+    ));
+    assert!(matches!(
+        PublicKey::from_der(&rsa.public_key_to_der().unwrap()),
+        Err(Error::UnsupportedKeyType)
+    ));
+}
@@ -0,0 +1,189 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    decrypt_with_passphrase, encrypt_for_recipients, list_recipients, Error, Kdf, Recipient,
+    RecipientKind,
+};
+#[cfg(feature = "openssl")]
+use colorsquares::{decrypt_with_private_key, PrivateKey};
+
+const FAST_KDF: Kdf = Kdf::Pbkdf2Sha256 { rounds: 1_000 };
+
+// Byte offset of the recipient count, after the fixed header
+const COUNT: usize = 8;
+
+#[test]
+fn every_passphrase_recipient_decrypts() {
+    let recipients = [
+        Recipient::Passphrase("first", FAST_KDF),
+        Recipient::Passphrase(
+            "second",
+            Kdf::Argon2id {
+                m_cost: 8,
+                t_cost: 1,
+                p_cost: 1,
+            },
+        ),
+    ];
+    let encrypted = encrypt_for_recipients("some input", &recipients).unwrap();
+    for passphrase in ["first", "second"] {
+        assert_eq!(
+            decrypt_with_passphrase(&encrypted, passphrase)
+                .unwrap()
+                .as_str(),
+            "some input"
+        );
+    }
+    assert!(matches!(
+        decrypt_with_passphrase(&encrypted, "third"),
+        Err(Error::AuthenticationFailed)
+    ));
+
+    let listed = list_recipients(&encrypted).unwrap();
+    assert_eq!(listed.len(), 2);
+    for info in listed {
+        assert_eq!(info.kind, RecipientKind::Passphrase);
+        assert!(info.key_id.is_empty());
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn public_key_recipients_decrypt_and_are_listed() {
+    let x25519 = PrivateKey::generate_x25519().unwrap();
+    let rsa = PrivateKey::generate_rsa(2048).unwrap();
+    let x25519_public = x25519.public_key().unwrap();
+    let rsa_public = rsa.public_key().unwrap();
+    let recipients = [
+        Recipient::PublicKey(&x25519_public),
+        Recipient::Passphrase("passphrase", FAST_KDF),
+        Recipient::PublicKey(&rsa_public),
+    ];
+    let encrypted = encrypt_for_recipients("some input", &recipients).unwrap();
+
+    for private_key in [&x25519, &rsa] {
+        assert_eq!(
+            decrypt_with_private_key(&encrypted, private_key)
+                .unwrap()
+                .as_str(),
+            "some input"
+        );
+    }
+    assert_eq!(
+        decrypt_with_passphrase(&encrypted, "passphrase")
+            .unwrap()
+            .as_str(),
+        "some input"
+    );
+
+    let listed = list_recipients(&encrypted).unwrap();
+    let kinds: Vec<RecipientKind> = listed.iter().map(|info| info.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            RecipientKind::X25519,
+            RecipientKind::Passphrase,
+            RecipientKind::Rsa
+        ]
+    );
+    assert_eq!(listed[0].key_id, x25519_public.key_id().unwrap());
+    assert_eq!(listed[2].key_id, rsa_public.key_id().unwrap());
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn other_private_key_is_not_a_recipient() {
+    let recipient = PrivateKey::generate_x25519().unwrap();
+    let public_key = recipient.public_key().unwrap();
+    let encrypted =
+        encrypt_for_recipients("some input", &[Recipient::PublicKey(&public_key)]).unwrap();
+
+    let other = PrivateKey::generate_x25519().unwrap();
+    assert!(matches!(
+        decrypt_with_private_key(&encrypted, &other),
+        Err(Error::NotARecipient)
+    ));
+}
+
+#[test]
+fn recipient_count_is_checked() {
+    assert!(matches!(
+        encrypt_for_recipients("some input", &[]),
+        Err(Error::InvalidRecipients)
+    ));
+
+    #[cfg(feature = "openssl")]
+    {
+        let private_key = PrivateKey::generate_x25519().unwrap();
+        let public_key = private_key.public_key().unwrap();
+        let recipients: Vec<Recipient> = (0..256)
+            .map(|_| Recipient::PublicKey(&public_key))
+            .collect();
+        assert!(matches!(
+            encrypt_for_recipients("some input", &recipients),
+            Err(Error::InvalidRecipients)
+        ));
+        assert!(encrypt_for_recipients("some input", &recipients[..255]).is_ok());
+    }
+
+    let passphrases: Vec<Recipient> = (0..9)
+        .map(|_| Recipient::Passphrase("passphrase", FAST_KDF))
+        .collect();
+    assert!(matches!(
+        encrypt_for_recipients("some input", &passphrases),
+        Err(Error::InvalidRecipients)
+    ));
+    assert!(encrypt_for_recipients("some input", &passphrases[..8]).is_ok());
+}
+
+#[test]
+fn out_of_bounds_kdf_is_rejected_when_encrypting() {
+    for kdf in [
+        Kdf::Pbkdf2Sha256 { rounds: 0 },
+        Kdf::Argon2id {
+            m_cost: (1 << 20) + 1,
+            t_cost: 1,
+            p_cost: 1,
+        },
+    ] {
+        assert!(matches!(
+            encrypt_for_recipients("some input", &[Recipient::Passphrase("passphrase", kdf)]),
+            Err(Error::InvalidKdfParameters)
+        ));
+    }
+}
+
+#[test]
+fn too_many_passphrase_stanzas_are_refused() {
+    let one = STANDARD
+        .decode(
+            encrypt_for_recipients(
+                "some input",
+                &[Recipient::Passphrase("passphrase", FAST_KDF)],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+    let two = encrypt_for_recipients(
+        "some input",
+        &[
+            Recipient::Passphrase("passphrase", FAST_KDF),
+            Recipient::Passphrase("passphrase", FAST_KDF),
+        ],
+    )
+    .unwrap();
+    let stanza_len = STANDARD.decode(two).unwrap().len() - one.len();
+
+    // Nine copies of the stanza, which `encrypt_for_recipients` never writes
+    let stanza = &one[COUNT + 1..COUNT + 1 + stanza_len];
+    let mut crafted = one[..COUNT].to_vec();
+    crafted.push(9);
+    for _ in 0..9 {
+        crafted.extend_from_slice(stanza);
+    }
+    crafted.extend_from_slice(&one[COUNT + 1 + stanza_len..]);
+    assert!(matches!(
+        decrypt_with_passphrase(&STANDARD.encode(&crafted), "passphrase"),
+        Err(Error::InvalidRecipients)
+    ));
+}
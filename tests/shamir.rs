@@ -0,0 +1,100 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{combine_images, combine_shares, split_secret, split_to_images, Error};
+
+// Offset of the key share: magic, version, threshold, group id and index
+const KEY_SHARE_OFFSET: usize = 3 + 1 + 1 + 16 + 1;
+
+fn tamper(share: &str, offset: usize) -> String {
+    let mut data = STANDARD.decode(share).unwrap();
+    data[offset] ^= 1;
+    STANDARD.encode(data)
+}
+
+#[test]
+fn any_threshold_shares_recover_the_secret() {
+    let shares = split_secret(b"launch code", 3, 5).unwrap();
+    assert_eq!(shares.len(), 5);
+
+    for a in 0..5 {
+        for b in a + 1..5 {
+            for c in b + 1..5 {
+                // In any order
+                let subset = [shares[c].as_str(), shares[a].as_str(), shares[b].as_str()];
+                assert_eq!(combine_shares(&subset).unwrap().as_bytes(), b"launch code");
+            }
+        }
+    }
+
+    let all: Vec<&str> = shares.iter().map(String::as_str).collect();
+    assert_eq!(combine_shares(&all).unwrap().as_bytes(), b"launch code");
+}
+
+#[test]
+fn fewer_than_threshold_shares_are_not_enough() {
+    let shares = split_secret(b"launch code", 3, 5).unwrap();
+    assert!(matches!(
+        combine_shares(&[&shares[0], &shares[1]]),
+        Err(Error::NotEnoughShares)
+    ));
+    // The same share twice still counts once
+    assert!(matches!(
+        combine_shares(&[&shares[0], &shares[1], &shares[1]]),
+        Err(Error::NotEnoughShares)
+    ));
+    assert!(matches!(combine_shares(&[]), Err(Error::NotEnoughShares)));
+}
+
+#[test]
+fn inconsistent_shares_are_detected() {
+    let shares = split_secret(b"launch code", 2, 3).unwrap();
+    let other = split_secret(b"launch code", 2, 3).unwrap();
+
+    // From another split
+    assert!(matches!(
+        combine_shares(&[&shares[0], &other[1]]),
+        Err(Error::InconsistentShares)
+    ));
+
+    // An altered key share, alone with the threshold and among extra shares
+    let altered = tamper(&shares[1], KEY_SHARE_OFFSET);
+    assert!(matches!(
+        combine_shares(&[&shares[0], &altered]),
+        Err(Error::InconsistentShares)
+    ));
+    assert!(matches!(
+        combine_shares(&[&shares[0], &shares[2], &altered]),
+        Err(Error::InconsistentShares)
+    ));
+
+    // An altered threshold
+    let altered = tamper(&shares[1], 4);
+    assert!(matches!(
+        combine_shares(&[&shares[0], &altered]),
+        Err(Error::InconsistentShares)
+    ));
+}
+
+#[test]
+fn threshold_must_be_between_two_and_the_share_count() {
+    assert!(matches!(
+        split_secret(b"x", 1, 3),
+        Err(Error::InvalidThreshold)
+    ));
+    assert!(matches!(
+        split_secret(b"x", 4, 3),
+        Err(Error::InvalidThreshold)
+    ));
+    assert!(split_secret(b"x", 255, 255).is_ok());
+}
+
+#[test]
+fn shares_round_trip_through_images() {
+    let images = split_to_images(b"launch code", 2, 3, "cardano").unwrap();
+    assert_eq!(
+        combine_images(&[&images[2], &images[0]])
+            .unwrap()
+            .as_bytes(),
+        b"launch code"
+    );
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "openssl")]
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use colorsquares::{
+    create_img, decrypt_with, encrypt_with, sign_envelope, signer_key_id, verify_envelope,
+    verify_image, Error, SigningKey,
+};
+use common::key;
+
+// Byte offsets in a default envelope: the issued at time in the header, and
+// the start of the payload
+const ISSUED_AT: usize = 8;
+const PAYLOAD: usize = 20;
+
+fn signers() -> [SigningKey; 2] {
+    [
+        SigningKey::generate_ed25519().unwrap(),
+        SigningKey::generate_p256().unwrap(),
+    ]
+}
+
+#[test]
+fn signed_envelopes_verify_and_decrypt() {
+    for signer in signers() {
+        let encrypted = encrypt_with("some input", &key()).unwrap();
+        let signed = sign_envelope(&encrypted, &signer).unwrap();
+        verify_envelope(&signed, &signer.verifying_key().unwrap()).unwrap();
+        assert_eq!(signer_key_id(&signed).unwrap(), signer.key_id().unwrap());
+        assert_eq!(
+            decrypt_with(&signed, &key()).unwrap().as_str(),
+            "some input"
+        );
+    }
+}
+
+#[test]
+fn wrong_public_key_is_rejected() {
+    let [ed25519, p256] = signers();
+    let encrypted = encrypt_with("some input", &key()).unwrap();
+    for (signer, other) in [
+        (&ed25519, SigningKey::generate_ed25519().unwrap()),
+        (&p256, SigningKey::generate_p256().unwrap()),
+        (&ed25519, p256.clone()),
+    ] {
+        let signed = sign_envelope(&encrypted, signer).unwrap();
+        assert!(matches!(
+            verify_envelope(&signed, &other.verifying_key().unwrap()),
+            Err(Error::InvalidSignature)
+        ));
+    }
+}
+
+#[test]
+fn tampered_header_or_payload_is_rejected() {
+    for signer in signers() {
+        let encrypted = encrypt_with("some input", &key()).unwrap();
+        let signed = STANDARD
+            .decode(sign_envelope(&encrypted, &signer).unwrap())
+            .unwrap();
+        for i in [ISSUED_AT, PAYLOAD, PAYLOAD + 20] {
+            let mut tampered = signed.clone();
+            tampered[i] ^= 0x01;
+            assert!(matches!(
+                verify_envelope(
+                    &STANDARD.encode(&tampered),
+                    &signer.verifying_key().unwrap()
+                ),
+                Err(Error::InvalidSignature)
+            ));
+        }
+    }
+}
+
+#[test]
+fn signing_twice_or_verifying_unsigned_fails() {
+    let signer = SigningKey::generate_ed25519().unwrap();
+    let encrypted = encrypt_with("some input", &key()).unwrap();
+    assert!(matches!(
+        verify_envelope(&encrypted, &signer.verifying_key().unwrap()),
+        Err(Error::MissingSignature)
+    ));
+    assert!(matches!(
+        signer_key_id(&encrypted),
+        Err(Error::MissingSignature)
+    ));
+
+    let signed = sign_envelope(&encrypted, &signer).unwrap();
+    assert!(matches!(
+        sign_envelope(&signed, &signer),
+        Err(Error::AlreadySigned)
+    ));
+}
+
+#[test]
+fn signed_image_verifies() {
+    for signer in signers() {
+        let encrypted = encrypt_with("some input", &key()).unwrap();
+        let signed = sign_envelope(&encrypted, &signer).unwrap();
+        let image = create_img(&signed, "issuer").unwrap();
+
+        let extracted = verify_image(&image, &signer.verifying_key().unwrap()).unwrap();
+        assert_eq!(extracted, signed);
+        assert_eq!(
+            decrypt_with(&extracted, &key()).unwrap().as_str(),
+            "some input"
+        );
+
+        let other = SigningKey::generate_ed25519().unwrap();
+        assert!(matches!(
+            verify_image(&image, &other.verifying_key().unwrap()),
+            Err(Error::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use colorsquares::{
+    decrypt_stream, encrypt_stream, CipherSuite, Error, Key, StreamDecryptor, StreamEncryptor,
+};
+use common::{key, key32};
+use std::io::{Read, Write};
+
+// Layout constants of the streamed format
+const HEADER_LEN: usize = 28;
+const CHUNK_SIZE: usize = 64 * 1024;
+const SEALED_CHUNK: usize = CHUNK_SIZE + 16;
+
+// Offsets of the suite and MAC ids in the header
+const SUITE: usize = 4;
+const MAC: usize = 5;
+
+fn input(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn encrypt(plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::new();
+    encrypt_stream(
+        &mut &plaintext[..],
+        &mut sealed,
+        &key32(),
+        CipherSuite::Aes256Gcm,
+    )
+    .unwrap();
+    sealed
+}
+
+fn decrypt(sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    decrypt_stream(sealed, &mut out, &key32())?;
+    Ok(out)
+}
+
+// Tampering is only noticed at the chunk it affects, so data from the chunks
+// before it may already have been written
+fn fails_authentication(sealed: &[u8]) -> bool {
+    matches!(decrypt(sealed), Err(Error::AuthenticationFailed))
+}
+
+#[test]
+fn round_trips_any_length() {
+    let lengths = [
+        0,
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        CHUNK_SIZE + 1,
+        2 * CHUNK_SIZE,
+        2 * CHUNK_SIZE + 7,
+    ];
+    for suite in [
+        CipherSuite::Aes128Gcm,
+        CipherSuite::Aes256Gcm,
+        CipherSuite::ChaCha20Poly1305,
+    ] {
+        let key = Key::new(&key32().as_bytes()[..suite.key_len()]);
+        for len in lengths {
+            let plaintext = input(len);
+            let mut sealed = Vec::new();
+            let written = encrypt_stream(&mut &plaintext[..], &mut sealed, &key, suite).unwrap();
+            assert_eq!(written, len as u64);
+
+            let mut out = Vec::new();
+            let read = decrypt_stream(&sealed[..], &mut out, &key).unwrap();
+            assert_eq!(read, len as u64);
+            assert_eq!(out, plaintext, "{:?}, {} bytes", suite, len);
+        }
+    }
+}
+
+#[test]
+fn reads_in_small_pieces() {
+    let plaintext = input(CHUNK_SIZE + 100);
+    let mut encryptor = StreamEncryptor::new(Vec::new(), &key32(), CipherSuite::Aes256Gcm).unwrap();
+    for piece in plaintext.chunks(1000) {
+        encryptor.write_all(piece).unwrap();
+    }
+    let sealed = encryptor.finish().unwrap();
+
+    let mut decryptor = StreamDecryptor::new(&sealed[..], &key32()).unwrap();
+    let mut out = Vec::new();
+    let mut piece = [0; 333];
+    loop {
+        let len = decryptor.read(&mut piece).unwrap();
+        if len == 0 {
+            break;
+        }
+        out.extend_from_slice(&piece[..len]);
+    }
+    assert_eq!(out, plaintext);
+}
+
+#[test]
+fn truncation_at_a_chunk_boundary_is_detected() {
+    let sealed = encrypt(&input(2 * CHUNK_SIZE + 7));
+    let truncated = &sealed[..HEADER_LEN + 2 * SEALED_CHUNK];
+    assert!(fails_authentication(truncated));
+    let truncated = &sealed[..HEADER_LEN + SEALED_CHUNK];
+    assert!(fails_authentication(truncated));
+}
+
+#[test]
+fn swapped_chunks_are_detected() {
+    let mut sealed = encrypt(&input(2 * CHUNK_SIZE + 7));
+    let (first, rest) = sealed[HEADER_LEN..].split_at_mut(SEALED_CHUNK);
+    first.swap_with_slice(&mut rest[..SEALED_CHUNK]);
+    assert!(fails_authentication(&sealed));
+}
+
+#[test]
+fn final_chunk_must_be_marked_last() {
+    // Dropped without `finish`, the encryptor has sealed its second chunk as
+    // one that more follow
+    let mut sealed = Vec::new();
+    let plaintext = input(2 * CHUNK_SIZE + 1);
+    let mut encryptor =
+        StreamEncryptor::new(&mut sealed, &key32(), CipherSuite::Aes256Gcm).unwrap();
+    encryptor.write_all(&plaintext).unwrap();
+    drop(encryptor);
+
+    assert_eq!(sealed.len(), HEADER_LEN + 2 * SEALED_CHUNK);
+    assert!(fails_authentication(&sealed));
+}
+
+#[test]
+fn chunk_after_the_last_is_detected() {
+    let mut sealed = encrypt(&input(CHUNK_SIZE + 7));
+    let first = sealed[HEADER_LEN..HEADER_LEN + SEALED_CHUNK].to_vec();
+    sealed.extend_from_slice(&first);
+    assert!(fails_authentication(&sealed));
+
+    let mut sealed = encrypt(b"short");
+    sealed.push(0);
+    assert!(fails_authentication(&sealed));
+}
+
+#[test]
+fn cbc_suite_is_rejected() {
+    let cbc_key = key();
+    assert!(matches!(
+        StreamEncryptor::new(Vec::new(), &cbc_key, CipherSuite::Aes128CbcHmacSha256),
+        Err(Error::UnsupportedSuite)
+    ));
+
+    let mut sealed = encrypt(b"short");
+    sealed[SUITE] = 1;
+    sealed[MAC] = 1;
+    assert!(matches!(
+        StreamDecryptor::new(&sealed[..], &cbc_key),
+        Err(Error::UnsupportedSuite)
+    ));
+}